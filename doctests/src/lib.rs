@@ -0,0 +1,29 @@
+//! Types deriving `ClosureInverter` with `gen_doctest`. Doc tests are compiled outside the crate
+//! defining them, so the examples generated here are run as a user's would be.
+
+use proc_lineq_derive::ClosureInverter;
+
+#[derive(ClosureInverter)]
+#[invert(expr = "|| a * 2 + 1", samples = [(7, 3), (11, 5)], gen_doctest = true)]
+pub struct Doubled;
+
+pub mod units {
+    use proc_lineq_derive::ClosureInverter;
+
+    #[derive(ClosureInverter)]
+    #[invert(expr = "|| a * 12", ty = "u32", samples = [(36, 3)], gen_doctest = true)]
+    pub struct Feet;
+}
+
+/// Private, so its example is marked `ignore` rather than failing to import it.
+#[allow(dead_code)]
+#[derive(ClosureInverter)]
+#[invert(expr = "|| a + 1", samples = [(4, 3)], gen_doctest = true)]
+struct Offset;
+
+/// Named, so not implemented through the trait. Its method is private, and its example is not
+/// run either.
+#[allow(dead_code)]
+#[derive(ClosureInverter)]
+#[invert(expr = "|| a * 3", name = "thirds", samples = [(9, 3)], gen_doctest = true)]
+pub struct Thirds;
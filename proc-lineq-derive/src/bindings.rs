@@ -0,0 +1,71 @@
+use syn::parse::{Parse, ParseStream};
+use syn::visit_mut::{self, VisitMut};
+use syn::{Expr, Ident, Token};
+
+/// A named constant declared with `let = "NAME = value"` on an invert attribute.
+pub(crate) struct LetBinding {
+    pub name: Ident,
+    pub value: Expr,
+}
+
+impl Parse for LetBinding {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let name = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let value = input.parse()?;
+        Ok(Self { name, value })
+    }
+}
+
+/// Replaces every use of a binding in `expr` with its value.
+///
+/// Bindings may refer to those declared before them. Once bindings are in use, any other bare
+/// identifier apart from the `free` variables is reported as undefined.
+pub(crate) fn substitute(
+    expr: &mut Expr,
+    bindings: &[LetBinding],
+    free: &[Ident],
+) -> syn::Result<()> {
+    let mut resolved: Vec<(Ident, Expr)> = vec![];
+    for binding in bindings {
+        let mut value = binding.value.clone();
+        replace(&mut value, &resolved, &[])?;
+        resolved.push((binding.name.clone(), value));
+    }
+    replace(expr, &resolved, free)
+}
+
+fn replace(expr: &mut Expr, resolved: &[(Ident, Expr)], free: &[Ident]) -> syn::Result<()> {
+    let mut substitution = Substitution {
+        resolved,
+        free,
+        error: None,
+    };
+    substitution.visit_expr_mut(expr);
+    substitution.error.map_or(Ok(()), Err)
+}
+
+struct Substitution<'a> {
+    resolved: &'a [(Ident, Expr)],
+    free: &'a [Ident],
+    error: Option<syn::Error>,
+}
+
+impl VisitMut for Substitution<'_> {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        if let Expr::Path(p) = e {
+            if let Some(ident) = p.path.get_ident() {
+                if let Some((_, value)) = self.resolved.iter().find(|(name, _)| name == ident) {
+                    *e = value.clone();
+                } else if !self.free.contains(ident) && self.error.is_none() {
+                    self.error = Some(syn::Error::new(
+                        ident.span(),
+                        format!("undefined name `{}`", ident),
+                    ));
+                }
+                return;
+            }
+        }
+        visit_mut::visit_expr_mut(self, e);
+    }
+}
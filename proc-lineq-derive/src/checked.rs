@@ -0,0 +1,41 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{BinOp, Expr, Type, UnOp};
+
+/// Lowers an inverse expression into one evaluating to `Option<ty>`, which is `None` whenever
+/// a step overflows the type or divides by zero.
+pub(crate) fn checked_expr(e: &Expr, ty: &Type) -> TokenStream {
+    match e {
+        Expr::Binary(b) => {
+            let left = checked_expr(&b.left, ty);
+            let right = checked_expr(&b.right, ty);
+            let checked = match b.op {
+                BinOp::Add(_) => quote!(<#ty>::checked_add(l, r)),
+                BinOp::Sub(_) => quote!(<#ty>::checked_sub(l, r)),
+                BinOp::Mul(_) => quote!(<#ty>::checked_mul(l, r)),
+                BinOp::Div(_) => quote!(<#ty>::checked_div(l, r)),
+                // `checked_shl` only rejects shifting past the width, not losing the high bits
+                BinOp::Shl(_) => {
+                    quote!(<#ty>::checked_pow(2, r as u32).and_then(|m| <#ty>::checked_mul(l, m)))
+                }
+                BinOp::Shr(_) => quote!(<#ty>::checked_shr(l, r as u32)),
+                _ => return quote!(::core::option::Option::Some::<#ty>(#e)),
+            };
+            quote!(match (#left, #right) {
+                (::core::option::Option::Some(l), ::core::option::Option::Some(r)) => #checked,
+                _ => ::core::option::Option::None,
+            })
+        }
+        Expr::Paren(p) => checked_expr(&p.expr, ty),
+        Expr::Group(g) => checked_expr(&g.expr, ty),
+        // Negating the minimum of a signed type overflows too
+        Expr::Unary(u) if matches!(u.op, UnOp::Neg(_)) => {
+            let operand = checked_expr(&u.expr, ty);
+            quote!(match #operand {
+                ::core::option::Option::Some(x) => <#ty>::checked_neg(x),
+                _ => ::core::option::Option::None,
+            })
+        }
+        _ => quote!(::core::option::Option::Some::<#ty>(#e)),
+    }
+}
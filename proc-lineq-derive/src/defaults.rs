@@ -0,0 +1,68 @@
+use crate::options::OptionPairs;
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::{Item, LitStr, Meta, Token};
+
+/// The input of `invert_defaults!`, options followed by the items they apply to.
+pub(crate) struct Defaults {
+    pub pairs: OptionPairs,
+    pub items: Vec<Item>,
+}
+
+impl Parse for Defaults {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let pairs = input.parse()?;
+        input.parse::<Token![;]>()?;
+        let mut items = vec![];
+        while !input.is_empty() {
+            items.push(input.parse()?);
+        }
+        Ok(Self { pairs, items })
+    }
+}
+
+/// Adds each default an `#[invert]` attribute leaves out to the attribute, on a struct or enum
+/// or on the items of an inline module.
+pub(crate) fn apply(item: &mut Item, defaults: &OptionPairs) -> syn::Result<()> {
+    let attrs = match item {
+        Item::Struct(s) => &mut s.attrs,
+        Item::Enum(e) => &mut e.attrs,
+        Item::Mod(m) => {
+            for item in m.content.iter_mut().flat_map(|(_, items)| items) {
+                apply(item, defaults)?;
+            }
+            return Ok(());
+        }
+        _ => return Ok(()),
+    };
+    for attr in attrs
+        .iter_mut()
+        .filter(|attr| attr.path().is_ident("invert"))
+    {
+        // Anything else is left for the derive to report
+        let Meta::List(list) = &mut attr.meta else {
+            continue;
+        };
+        let (formula, pairs) = list.parse_args_with(|input: ParseStream| {
+            let formula = if input.peek(LitStr) {
+                let formula: LitStr = input.parse()?;
+                if !input.is_empty() {
+                    input.parse::<Token![,]>()?;
+                }
+                Some(formula)
+            } else {
+                None
+            };
+            Ok((formula, input.parse::<OptionPairs>()?.0))
+        })?;
+        let set = pairs.iter().map(|(key, value)| quote!(#key = #value));
+        let missing = defaults
+            .0
+            .iter()
+            .filter(|(key, _)| !pairs.iter().any(|(set, _)| set == key))
+            .map(|(key, value)| quote!(#key = #value));
+        let formula = formula.iter();
+        list.tokens = quote!(#(#formula,)* #(#set,)* #(#missing),*);
+    }
+    Ok(())
+}
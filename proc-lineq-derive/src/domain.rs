@@ -0,0 +1,99 @@
+use crate::literals;
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::{Expr, ExprLit, ExprRange, Lit, LitStr, RangeLimits, Type};
+
+/// The range of inputs a generated function is declared to accept, with an inclusive lower bound.
+pub(crate) struct Domain {
+    pub lower: Option<Expr>,
+    pub upper: Option<Expr>,
+    /// Whether `upper` is itself in the domain, as in `0..=100`, rather than excluded.
+    pub inclusive: bool,
+}
+
+impl Domain {
+    /// Parses a range such as `"0..=100"`, `"1..10"` or `"5.."`.
+    pub fn parse(s: &LitStr) -> syn::Result<Self> {
+        let range = s.parse::<ExprRange>()?;
+        let lower = range.start.map(|e| *e);
+        let upper = range.end.map(|e| *e);
+        let inclusive = matches!(range.limits, RangeLimits::Closed(_));
+        if lower.is_none() && upper.is_none() {
+            return Err(syn::Error::new(
+                s.span(),
+                "domain must have at least one bound",
+            ));
+        }
+        Ok(Self {
+            lower,
+            upper,
+            inclusive,
+        })
+    }
+
+    /// Returns true if the lower bound is a literal which excludes negative values.
+    pub fn is_non_negative(&self) -> bool {
+        matches!(
+            &self.lower,
+            Some(Expr::Lit(ExprLit {
+                lit: Lit::Int(_),
+                ..
+            }))
+        )
+    }
+
+    /// Rebinds `input` clamped into the domain. An excluded upper bound is clamped to the value
+    /// below it, so only for an integer `ty`.
+    pub fn clamp(&self, ty: &Type, input: &Ident) -> syn::Result<TokenStream> {
+        let lower = self.lower.as_ref().map(|lower| quote!(.max(#lower)));
+        let upper = match &self.upper {
+            Some(upper) if self.inclusive => Some(quote!(.min(#upper))),
+            Some(upper) if literals::is_integer(ty) => Some(quote!(.min(#upper - 1))),
+            Some(upper) => {
+                return Err(syn::Error::new_spanned(
+                    upper,
+                    "saturate_domain requires an inclusive upper bound, such as `0.0..=1.0`, \
+                     unless the type is an integer",
+                ))
+            }
+            None => None,
+        };
+        Ok(quote!(let #input = #input #lower #upper;))
+    }
+
+    /// A `proptest` strategy over the values of `ty` inside the domain, leaving out zero when
+    /// `nonzero` is set.
+    pub fn strategy(domain: Option<&Self>, ty: &Type, nonzero: bool) -> TokenStream {
+        let lower = match domain.and_then(|domain| domain.lower.as_ref()) {
+            Some(lower) => quote!(#lower),
+            None => quote!(<#ty>::MIN),
+        };
+        let upper = match domain.and_then(|domain| domain.upper.as_ref()) {
+            Some(upper) => quote!(#upper),
+            None => quote!(<#ty>::MAX),
+        };
+        let nonzero = nonzero.then(|| quote!(let lower = lower.max(1);));
+        let range = match domain {
+            Some(domain) if domain.upper.is_some() && !domain.inclusive => quote!(lower..upper),
+            _ => quote!(lower..=upper),
+        };
+        quote!(
+            let lower: #ty = #lower;
+            #nonzero
+            let upper: #ty = #upper;
+            #range
+        )
+    }
+
+    /// An expression which is true when `input` lies inside the domain.
+    pub fn contains(&self, input: &Ident) -> TokenStream {
+        match (&self.lower, &self.upper, self.inclusive) {
+            (Some(lower), Some(upper), true) => quote!((#lower..=#upper).contains(&#input)),
+            (Some(lower), Some(upper), false) => quote!((#lower..#upper).contains(&#input)),
+            (Some(lower), None, _) => quote!(#lower <= #input),
+            (None, Some(upper), true) => quote!(#input <= #upper),
+            (None, Some(upper), false) => quote!(#input < #upper),
+            (None, None, _) => quote!(true),
+        }
+    }
+}
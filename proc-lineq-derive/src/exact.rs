@@ -0,0 +1,59 @@
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::quote;
+use syn::{BinOp, Expr, Type};
+
+/// Lowers an inverse expression into one which returns `InversionError::NotExact` from the
+/// enclosing function whenever a division or a shift to the right leaves a remainder.
+pub(crate) fn exact_expr(e: &Expr, ty: &Type) -> TokenStream {
+    // Hygienic, so the divisor of a formula's own `value` is not the dividend
+    let value = Ident::new("value", Span::mixed_site());
+    let divisor = Ident::new("divisor", Span::mixed_site());
+    let shift = Ident::new("shift", Span::mixed_site());
+    match e {
+        Expr::Binary(b) if matches!(b.op, BinOp::Div(_)) => {
+            let left = exact_expr(&b.left, ty);
+            let right = exact_expr(&b.right, ty);
+            quote!({
+                let #value: #ty = #left;
+                let #divisor: #ty = #right;
+                if #value % #divisor != 0 {
+                    return ::core::result::Result::Err(::proc_lineq::InversionError::NotExact {
+                        value: #value,
+                        divisor: #divisor,
+                    });
+                }
+                #value / #divisor
+            })
+        }
+        // A shift to the right divides by a power of two
+        Expr::Binary(b) if matches!(b.op, BinOp::Shr(_)) => {
+            let left = exact_expr(&b.left, ty);
+            let right = exact_expr(&b.right, ty);
+            quote!({
+                let #value: #ty = #left;
+                let #shift: #ty = #right;
+                let #divisor: #ty = 1 << #shift;
+                if #value % #divisor != 0 {
+                    return ::core::result::Result::Err(::proc_lineq::InversionError::NotExact {
+                        value: #value,
+                        divisor: #divisor,
+                    });
+                }
+                #value >> #shift
+            })
+        }
+        Expr::Binary(b) => {
+            let left = exact_expr(&b.left, ty);
+            let op = &b.op;
+            let right = exact_expr(&b.right, ty);
+            quote!((#left #op #right))
+        }
+        Expr::Paren(p) => exact_expr(&p.expr, ty),
+        Expr::Unary(u) => {
+            let op = &u.op;
+            let operand = exact_expr(&u.expr, ty);
+            quote!((#op #operand))
+        }
+        _ => quote!(#e),
+    }
+}
@@ -0,0 +1,77 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Expr, Ident, Type};
+
+/// Builds a function taking each named constant of the inverse as a parameter and returning
+/// the inverse as a closure capturing them.
+///
+/// Parameters are the constants' names in lower case, so `SCALE` is taken as `scale`, in the
+/// order they appear in the formula. A name which is a keyword in lower case, such as `TYPE`, is
+/// taken as a raw identifier, `r#type`.
+pub(crate) fn factory(
+    name: &Ident,
+    forward: &Expr,
+    solve_for: &Ident,
+    inverse: &Expr,
+    target: &Ident,
+    ty: &Type,
+) -> syn::Result<TokenStream> {
+    let mut params = vec![];
+    lift_constants(&mut forward.clone(), solve_for, &mut params)?;
+    let mut body = inverse.clone();
+    lift_constants(&mut body, target, &mut params)?;
+    Ok(quote!(
+        fn #name(#(#params: #ty),*) -> impl Fn(#ty) -> #ty {
+            move |#target| #body
+        }
+    ))
+}
+
+/// Renames each named constant used as an operand to its parameter, collecting the parameters
+/// in order of first use.
+fn lift_constants(e: &mut Expr, target: &Ident, params: &mut Vec<Ident>) -> syn::Result<()> {
+    match e {
+        Expr::Binary(b) => {
+            lift_constants(&mut b.left, target, params)?;
+            lift_constants(&mut b.right, target, params)
+        }
+        Expr::Cast(c) => lift_constants(&mut c.expr, target, params),
+        Expr::MethodCall(m) => lift_constants(&mut m.receiver, target, params),
+        Expr::Paren(p) => lift_constants(&mut p.expr, target, params),
+        Expr::Unary(u) => lift_constants(&mut u.expr, target, params),
+        Expr::Path(p) => {
+            let Some(ident) = p.path.get_ident() else {
+                return Ok(());
+            };
+            if ident == target {
+                return Ok(());
+            }
+            let param = param_ident(ident)?;
+            if !params.contains(&param) {
+                params.push(param.clone());
+            }
+            *e = syn::parse_quote!(#param);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The parameter a constant is taken as, its name in lower case.
+fn param_ident(constant: &Ident) -> syn::Result<Ident> {
+    let name = constant.to_string().to_lowercase();
+    if syn::parse_str::<Ident>(&name).is_ok() {
+        return Ok(Ident::new(&name, constant.span()));
+    }
+    // Keywords which cannot be raw identifiers either
+    if matches!(name.as_str(), "self" | "super" | "crate") {
+        return Err(syn::Error::new(
+            constant.span(),
+            format!(
+                "`{}` cannot be a factory parameter, as `{}` is a keyword",
+                constant, name
+            ),
+        ));
+    }
+    Ok(Ident::new_raw(&name, constant.span()))
+}
@@ -0,0 +1,61 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::{BinOp, Expr};
+
+/// Lowers an inverse into `::proc_lineq::Op` values, innermost first, the order in which they
+/// are evaluated.
+pub(crate) fn ops(e: &Expr, target: &Ident) -> syn::Result<Vec<TokenStream>> {
+    let mut ops = vec![];
+    collect(e, target, &mut ops)?;
+    Ok(ops)
+}
+
+/// Collects each operation on the path from the root down to the target, failing on anything
+/// an `Op` cannot describe.
+fn collect(e: &Expr, target: &Ident, ops: &mut Vec<TokenStream>) -> syn::Result<()> {
+    match e {
+        Expr::Binary(b) if contains(&b.left, target) => {
+            collect(&b.left, target, ops)?;
+            let n = &b.right;
+            ops.push(match b.op {
+                BinOp::Add(_) => quote!(::proc_lineq::Op::AddConst(#n)),
+                BinOp::Sub(_) => quote!(::proc_lineq::Op::SubConst(#n)),
+                BinOp::Mul(_) => quote!(::proc_lineq::Op::MulConst(#n)),
+                BinOp::Div(_) => quote!(::proc_lineq::Op::DivConst(#n)),
+                _ => return Err(unsupported(e)),
+            });
+            Ok(())
+        }
+        Expr::Binary(b) if contains(&b.right, target) => {
+            collect(&b.right, target, ops)?;
+            let n = &b.left;
+            ops.push(match b.op {
+                BinOp::Add(_) => quote!(::proc_lineq::Op::AddConst(#n)),
+                BinOp::Sub(_) => quote!(::proc_lineq::Op::SubFrom(#n)),
+                BinOp::Mul(_) => quote!(::proc_lineq::Op::MulConst(#n)),
+                BinOp::Div(_) => quote!(::proc_lineq::Op::ReciprocalInto(#n)),
+                _ => return Err(unsupported(e)),
+            });
+            Ok(())
+        }
+        Expr::Paren(p) => collect(&p.expr, target, ops),
+        Expr::Path(p) if p.path.is_ident(target) => Ok(()),
+        _ => Err(unsupported(e)),
+    }
+}
+
+fn unsupported(e: &Expr) -> syn::Error {
+    syn::Error::new_spanned(
+        e,
+        "emit_ir only supports `+`, `-`, `*` and `/` in the inverse",
+    )
+}
+
+fn contains(e: &Expr, target: &Ident) -> bool {
+    match e {
+        Expr::Binary(b) => contains(&b.left, target) || contains(&b.right, target),
+        Expr::Paren(p) => contains(&p.expr, target),
+        Expr::Path(p) => p.path.is_ident(target),
+        _ => false,
+    }
+}
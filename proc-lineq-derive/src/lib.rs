@@ -1,41 +1,1126 @@
 #![warn(clippy::panic, clippy::str_to_string, clippy::panicking_unwrap)]
 
+mod bindings;
+mod checked;
+mod defaults;
+mod domain;
+mod exact;
+mod factory;
+mod ir;
+mod literals;
+mod markers;
+mod nonzero;
+mod optimize;
+mod options;
+mod samples;
+mod steps;
+mod trace;
+mod unicode;
+mod variant;
+mod verify;
+
+use defaults::Defaults;
+use domain::Domain;
+use options::{Formula, InvertOptions, OverflowCheck};
 use proc_lineq::ClosureInverter;
 use proc_macro::TokenStream;
-use quote::{format_ident, quote};
-use syn::{parse, parse_macro_input, DeriveInput, LitStr, Meta};
+use proc_macro2::{Ident, Span};
+use quote::{format_ident, quote, quote_spanned};
+use syn::parse::{Parse, ParseStream};
+use syn::spanned::Spanned;
+use syn::visit::Visit;
+use syn::{
+    parse2, parse_macro_input, DeriveInput, Expr, ExprClosure, ExprLit, ExprRange, Lit, LitStr,
+    Pat, PatType, RangeLimits, Token, UnOp,
+};
 
 #[proc_macro_derive(ClosureInverter, attributes(invert))]
 pub fn is_closure_inverter(tokens: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(tokens as DeriveInput);
-    let struct_ident = ast.ident;
-    if ast.attrs.len() == 1 {
-        let attr = &ast.attrs[0];
-        if attr.path().is_ident("invert") {
-            attr.meta.require_list().expect("Unwrap to error");
-            // Parse the meta into a string
-            match &attr.meta {
-                Meta::List(meta_list) => {
-                    let closure_str = parse::<LitStr>(meta_list.tokens.clone().into())
-                        .expect("Did not receive a string");
-                    let closure = closure_str.parse::<syn::ExprClosure>().unwrap();
-                    let eq = ClosureInverter::new(format_ident!("a"), format_ident!("b"));
-                    let result = eq.solve(&closure).unwrap();
-                    let return_stream = quote!(
-                    impl #struct_ident {
-                        fn calculate(value: usize) -> usize {
-                            let closure = #result;
-                            closure(value)
-                        }
-                    });
-                    return_stream.into()
+    let mut expanded = proc_macro2::TokenStream::new();
+    // Methods and constants generated so far, which a later attribute may not generate again
+    let mut items: Vec<Ident> = vec![];
+    for attr in ast
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("invert"))
+    {
+        let meta_list = match attr.meta.require_list() {
+            Ok(meta_list) => meta_list,
+            Err(_) => {
+                return syn::Error::new_spanned(&attr.meta, "expected `#[invert(...)]`")
+                    .to_compile_error()
+                    .into()
+            }
+        };
+        let options = match parse2::<InvertOptions>(meta_list.tokens.clone()) {
+            Ok(options) => options,
+            Err(e) => return e.to_compile_error().into(),
+        };
+        for item in options.item_names() {
+            if items.contains(&item) {
+                return syn::Error::new_spanned(
+                    attr,
+                    format!(
+                        "`{}` is already generated by another invert attribute",
+                        item
+                    ),
+                )
+                .to_compile_error()
+                .into();
+            }
+            items.push(item);
+        }
+        expanded.extend(expand(&ast, &options).unwrap_or_else(syn::Error::into_compile_error));
+    }
+    if items.is_empty() {
+        return syn::Error::new(
+            Span::call_site(),
+            "ClosureInverter requires an invert attribute",
+        )
+        .to_compile_error()
+        .into();
+    }
+    expanded.into()
+}
+
+/// Generates the methods of a single `#[invert(...)]` attribute, in an impl block of their own.
+fn expand(ast: &DeriveInput, options: &InvertOptions) -> syn::Result<proc_macro2::TokenStream> {
+    let struct_ident = &ast.ident;
+    // On an enum the inverse is applied to the value wrapped by the named variant
+    let variant = variant::resolve(&ast.data, options.variant.as_ref())?;
+
+    let default_solve_for = options
+        .solve_for
+        .clone()
+        .unwrap_or_else(|| format_ident!("a"));
+    // Hygienic, so it cannot collide with the closure's other parameters
+    let target_ident = Ident::new("b", Span::mixed_site());
+    // The value passed to the generated methods, hygienic for the same reason
+    let input = Ident::new("value", Span::mixed_site());
+    let mut closure = match &options.formula {
+        Formula::Expr(expr) => parse_formula(expr)?,
+        Formula::Pipeline(stages) => {
+            let closures = stages
+                .iter()
+                .map(parse_formula)
+                .collect::<syn::Result<Vec<_>>>()?;
+            let eq = ClosureInverter::new(default_solve_for.clone(), target_ident.clone());
+            eq.compose(&closures)
+                .map_err(|e| syn::Error::new(stages[0].span(), e))?
+        }
+    };
+    // Without solve_for, a closure's single parameter names the variable
+    let solve_for = match (&options.solve_for, ClosureInverter::bound_param(&closure)) {
+        (Some(solve_for), _) => solve_for.clone(),
+        (None, Ok(Some(param))) => param,
+        (None, Ok(None)) => default_solve_for,
+        (None, Err(e)) => return Err(syn::Error::new_spanned(&closure.inputs, e)),
+    };
+    // Parameters other than solve_for become extra parameters of calculate. A closure without
+    // any takes the other lower-case names in its formula, so `|| a * k + c` takes `k` and `c`.
+    let params = match closure_params(&closure)? {
+        params if params.is_empty() => free_params(&closure.body, &solve_for, &options.lets),
+        params => params,
+    };
+    if !params.is_empty() && !params.contains(&solve_for) {
+        return Err(syn::Error::new(
+            solve_for.span(),
+            format!("`{}` is not a parameter of the closure", solve_for),
+        ));
+    }
+    let extra_params: Vec<&Ident> = params.iter().filter(|param| **param != solve_for).collect();
+    if !extra_params.is_empty()
+        && (options.gen_test.is_some()
+            || variant.is_some()
+            || options.bijection_struct
+            || options.emit_ir
+            || options.verify)
+    {
+        return Err(syn::Error::new(
+            extra_params[0].span(),
+            "gen_test, variant, bijection_struct, emit_ir and verify cannot be used with closures taking several parameters",
+        ));
+    }
+
+    if !options.lets.is_empty() {
+        let mut free = params.clone();
+        free.push(solve_for.clone());
+        bindings::substitute(&mut closure.body, &options.lets, &free)?;
+    }
+    // Without a type, a suffixed literal names it
+    let ty = &options.ty.clone().unwrap_or_else(|| {
+        if let Some(ty) = literals::suffix_type(&closure.body) {
+            ty
+        } else if literals::contains_float(&closure.body) {
+            syn::parse_quote!(f64)
+        } else {
+            syn::parse_quote!(usize)
+        }
+    });
+    literals::normalize_literals(&mut closure.body, ty, options.coerce_literals)?;
+    let mut eq = ClosureInverter::new(solve_for.clone(), target_ident.clone());
+    if options.domain.as_ref().is_some_and(|d| d.is_non_negative()) {
+        eq = eq.non_negative();
+    }
+    if options.disjoint {
+        eq = eq.disjoint();
+    }
+    let formula_span = match &options.formula {
+        Formula::Expr(expr) => expr.span(),
+        Formula::Pipeline(stages) => stages[0].span(),
+    };
+    // A type given by `ty` is checked with the other options
+    if options.exact && !literals::is_integer(ty) {
+        return Err(syn::Error::new(
+            formula_span,
+            format!(
+                "exact requires a primitive integer type, but the formula's type is `{}`",
+                quote!(#ty)
+            ),
+        ));
+    }
+    require_integer_pow(&closure.body, ty)?;
+    if options.require_affine {
+        eq.require_affine(&closure)
+            .map_err(|e| syn::Error::new(formula_span, e))?;
+    }
+    let mut result = eq
+        .solve(&closure)
+        .map_err(|e| syn::Error::new(formula_span, e))?;
+    markers::strip_const_exprs(&mut result.body)?;
+    markers::strip_const_exprs(&mut closure.body)?;
+    let forward = ClosureInverter::body(&closure).map_err(|e| syn::Error::new(formula_span, e))?;
+    // Both are checked by items outside the impl, where `Self` is not defined
+    if options.verify || options.gen_test.is_some() {
+        if let Some(span) = verify::self_path(forward) {
+            return Err(syn::Error::new(
+                span,
+                "verify and gen_test check the formula outside the impl, so it cannot refer to \
+                 `Self`; name the type instead",
+            ));
+        }
+    }
+    if options.optimize {
+        optimize::shift_divisions(&mut result.body, ty);
+    }
+    if literals::is_unsigned(ty) && negates(&result.body) {
+        return Err(syn::Error::new(
+            formula_span,
+            format!(
+                "the inverse negates a value, which the unsigned `{}` cannot represent",
+                quote!(#ty)
+            ),
+        ));
+    }
+
+    // Flags an inverse which overflows for more than half of the type's values
+    let overflow_warning = match &options.overflow_check {
+        Some(check) => {
+            let eq = ClosureInverter::new(solve_for.clone(), target_ident.clone());
+            match eq.overflow_fraction(&closure, ty) {
+                Ok(Some(fraction)) if fraction > 0.5 => {
+                    let message = format!(
+                        "the inverse overflows for {:.1}% of `{}` inputs",
+                        fraction * 100.0,
+                        quote!(#ty)
+                    );
+                    match check {
+                        OverflowCheck::Deny => return Err(syn::Error::new(formula_span, message)),
+                        // Stable proc macros cannot emit warnings directly
+                        // Spanned on the formula, as lints are not
+                        // reported inside external macros
+                        OverflowCheck::Warn => quote_spanned!(formula_span=>
+                            const _: () = {
+                                #[deprecated(note = #message)]
+                                struct InverseOverflows;
+                                let _ = InverseOverflows;
+                            };
+                        ),
+                    }
                 }
-                _ => unreachable!(),
+                _ => quote!(),
             }
+        }
+        None => quote!(),
+    };
+
+    let saturate = match &options.domain {
+        Some(domain) if options.saturate_domain => domain.clamp(ty, &input)?,
+        _ => quote!(),
+    };
+
+    let calculate_ident = options.method("");
+    // `calculate` implements `Invertible` when it maps the formula's type to itself
+    let invertible = options.variants.plain
+        && options.name.is_none()
+        && options.factory.is_none()
+        && variant.is_none()
+        && extra_params.is_empty()
+        && options.result_wrap.is_none()
+        && options.uom.is_none()
+        && !options.nonzero_input
+        && !options.option_input
+        && !options.deref_input
+        && !options.exact
+        && !options.const_fn;
+    // Under debug builds, assert the input is one the inverse can be evaluated on
+    let bounds_check = if options.debug_bounds {
+        // Float arithmetic has no checked form, as it does not overflow
+        let in_inverse = literals::is_integer(ty).then(|| {
+            let checked = checked::checked_expr(&result.body, ty);
+            quote!(debug_assert!(
+                { let #target_ident = #input; #checked }.is_some(),
+                "{}: input {} is outside the domain of the inverse",
+                stringify!(#calculate_ident),
+                #input
+            );)
+        });
+        let in_domain = options.domain.as_ref().map(|domain| {
+            let contains = domain.contains(&input);
+            quote!(debug_assert!(
+                #contains,
+                "{}: input {} is outside the declared domain",
+                stringify!(#calculate_ident),
+                #input
+            );)
+        });
+        quote!(#in_domain #in_inverse)
+    } else {
+        quote!()
+    };
+    // Gates every generated item on the same predicate
+    let cfg = options.cfg.as_ref().map(|cfg| quote!(#[cfg(#cfg)]));
+    let roundtrip_test = options.gen_test.as_ref().map(|test_ident| {
+        // Without the plain form the checked one is expected to succeed
+        let (method, unwrap) = if options.variants.plain && !options.option_input {
+            (calculate_ident.clone(), quote!())
+        } else if options.variants.plain {
+            (calculate_ident.clone(), quote!(.unwrap()))
+        } else {
+            (options.method("checked"), quote!(.unwrap()))
+        };
+        let arg = if options.option_input {
+            quote!(::core::option::Option::Some(x))
         } else {
-            quote!(compile_error!("ClosureInverter requires a single invert attribute");).into()
+            quote!(x)
+        };
+        let mut inverse = match &variant {
+            Some(variant) => {
+                quote!(#struct_ident::#variant(x).#method().unwrap())
+            }
+            None if invertible => quote!(<#struct_ident as ::proc_lineq::Invertible>::calculate(x)),
+            None => quote!(#struct_ident::#method(#arg)#unwrap),
+        };
+        if options.result_wrap.is_some() {
+            inverse = quote!(#inverse.0);
+        }
+        verify::round_trip_test(test_ident, forward, inverse, &solve_for, ty, cfg.as_ref())
+    });
+    let trace = options
+        .trace_calls
+        .then(|| trace::trace_call(&calculate_ident, &input));
+    let (output_ty, output) = match (&options.result_wrap, &options.uom) {
+        // Spanned on the option, where rustc reports a `result_wrap` which is not a tuple struct
+        (Some(wrap), _) => (quote!(#wrap), quote_spanned!(wrap.span()=> #wrap(result))),
+        (None, Some((_, uom_out))) => (
+            quote!(#uom_out),
+            quote!(#uom_out {
+                dimension: ::core::marker::PhantomData,
+                units: ::core::marker::PhantomData,
+                value: result,
+            }),
+        ),
+        (None, None) => (quote!(#ty), quote!(result)),
+    };
+    // A quantity's value is stored in base units, which the formula acts on
+    let (input_ty, unwrap_input) = match &options.uom {
+        Some((uom_in, _)) => (quote!(#uom_in), quote!(let #input: #ty = #input.value;)),
+        None if options.nonzero_input => {
+            let nonzero = nonzero::nonzero_type(ty).ok_or_else(|| {
+                syn::Error::new_spanned(ty, "nonzero_input requires a primitive integer type")
+            })?;
+            if !nonzero::divides_by(&result.body, &target_ident) {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "nonzero_input requires a formula whose inverse divides by the input",
+                ));
+            }
+            (quote!(#nonzero), quote!(let #input: #ty = #input.get();))
         }
+        None if options.deref_input => (
+            quote!(&#ty),
+            quote!(let #input: #ty = ::core::clone::Clone::clone(#input);),
+        ),
+        None => (quote!(#ty), quote!()),
+    };
+    // With option_input, `None` is passed straight through. The checked form
+    // already returns an `Option`, so its `None`s are merged into one.
+    let (value_ty, unwrap_option) = if options.option_input {
+        (
+            quote!(::core::option::Option<#input_ty>),
+            quote!(let #input = #input?;),
+        )
+    } else {
+        (quote!(#input_ty), quote!())
+    };
+    let (plain_ty, plain_output) = if options.option_input {
+        (
+            quote!(::core::option::Option<#output_ty>),
+            quote!(::core::option::Option::Some(#output)),
+        )
     } else {
-        quote!(compile_error!("ClosureInverter requires a single invert attribute");).into()
+        (quote!(#output_ty), quote!(#output))
+    };
+    // User-defined finishing applied to the result of the inverse
+    let post_fn = options
+        .post_fn
+        .as_ref()
+        .map(|post_fn| quote!(let result = #post_fn(result);));
+    // Documents the samples as an example, for `cargo test --doc`. Examples are compiled outside
+    // the crate, so only one calling the `calculate` of a public type, which is public as it
+    // implements the trait, can be run.
+    let doc = options.gen_doctest.then(|| {
+        samples::doc_attrs(
+            struct_ident,
+            &calculate_ident,
+            invertible && matches!(ast.vis, syn::Visibility::Public(_)),
+            variant.as_ref(),
+            options.result_wrap.is_some(),
+            &options.samples,
+        )
+    });
+    // Typed, as negation cannot be applied to a value of unknown type
+    let inverse = &result.body;
+    let constness = options.const_fn.then(|| quote!(const));
+    // With exact, a division leaving a remainder returns an error instead
+    let (plain_ty, plain_output, evaluate) = if options.exact {
+        let exact = exact::exact_expr(inverse, ty);
+        (
+            quote!(::core::result::Result<#plain_ty, ::proc_lineq::InversionError<#ty>>),
+            quote!(::core::result::Result::Ok(#plain_output)),
+            quote!(
+                let #target_ident: #ty = #input;
+                let result: #ty = #exact;
+            ),
+        )
+    } else if options.const_fn {
+        // Closures cannot be called in a `const fn`
+        (
+            plain_ty,
+            plain_output,
+            quote!(
+                let #target_ident: #ty = #input;
+                let result: #ty = #inverse;
+            ),
+        )
+    } else {
+        (
+            plain_ty,
+            plain_output,
+            quote!(
+                let closure = |#target_ident: #ty| #inverse;
+                let result = closure(#input);
+            ),
+        )
+    };
+    let calculate = options.variants.plain.then(|| {
+        let calculate = quote!(
+            #constness fn #calculate_ident(#input: #value_ty #(, #extra_params: #ty)*) -> #plain_ty {
+                #unwrap_option
+                #unwrap_input
+                #saturate
+                #bounds_check
+                #evaluate
+                #post_fn
+                #trace
+                #plain_output
+            }
+        );
+        match &variant {
+            Some(variant) => {
+                let method = variant::method(
+                    variant,
+                    &calculate_ident,
+                    constness.clone(),
+                    &output_ty,
+                    calculate,
+                    false,
+                );
+                quote!(#doc #method)
+            }
+            None => quote!(#doc #calculate),
+        }
+    });
+    // The same inverse lowered to checked arithmetic
+    let calculate_checked = options.variants.checked.then(|| {
+        let checked_ident = options.method("checked");
+        let checked = checked::checked_expr(&result.body, ty);
+        let calculate_checked = quote!(
+            fn #checked_ident(
+                #input: #value_ty #(, #extra_params: #ty)*
+            ) -> ::core::option::Option<#output_ty> {
+                #unwrap_option
+                #unwrap_input
+                #saturate
+                let #target_ident = #input;
+                let result = #checked?;
+                #post_fn
+                #trace
+                ::core::option::Option::Some(#output)
+            }
+        );
+        match &variant {
+            Some(variant) => variant::method(
+                variant,
+                &checked_ident,
+                None,
+                &output_ty,
+                calculate_checked,
+                true,
+            ),
+            None => calculate_checked,
+        }
+    });
+    // Each intermediate value of the inverse, labelled with its operation
+    let calculate_steps = if options.steps {
+        let steps = steps::method(
+            &options.method("steps"),
+            quote!(#input: #input_ty #(, #extra_params: #ty)*),
+            quote!(#unwrap_input #saturate),
+            &result.body,
+            &input,
+            &target_ident,
+            ty,
+        )
+        // The inverse is generated, so the error is reported at the formula
+        .map_err(|e| syn::Error::new(formula_span, e))?;
+        Some(steps)
+    } else {
+        None
+    };
+    // Only unsigned types have their non-zero values in one range
+    let strategy = match &options.gen_strategy {
+        Some(_) if options.nonzero_input && !literals::is_unsigned(ty) => {
+            return Err(syn::Error::new_spanned(
+                ty,
+                "gen_strategy with nonzero_input requires an unsigned type",
+            ))
+        }
+        Some(name) => {
+            let range = Domain::strategy(options.domain.as_ref(), ty, options.nonzero_input);
+            quote!(
+                fn #name() -> impl ::proptest::strategy::Strategy<Value = #ty> {
+                    #range
+                }
+            )
+        }
+        None => quote!(),
+    };
+    let verification = if options.verify {
+        Some(verify::verification(
+            forward,
+            &result.body,
+            &solve_for,
+            &target_ident,
+            ty,
+            cfg.as_ref(),
+            formula_span,
+        )?)
+    } else {
+        None
+    };
+    // The formula itself, for checking the inverse against
+    let forward_fn = options.forward.then(|| {
+        quote!(
+            fn forward(#input: #ty #(, #extra_params: #ty)*) -> #ty {
+                let #solve_for = #input;
+                #forward
+            }
+        )
+    });
+    // The formula and its inverse as function pointers
+    let bijection = options.bijection_struct.then(|| {
+        let inverse = &result.body;
+        quote!(
+            const BIJECTION: ::proc_lineq::Bijection<#ty> = ::proc_lineq::Bijection {
+                forward: |#solve_for: #ty| #forward,
+                inverse: |#target_ident: #ty| #inverse,
+            };
+        )
+    });
+    // The inverse as operations for an interpreter
+    let ops = if options.emit_ir {
+        let ops = ir::ops(&result.body, &target_ident)?;
+        Some(quote!(
+            const OPS: &'static [::proc_lineq::Op<#ty>] = &[#(#ops),*];
+        ))
+    } else {
+        None
+    };
+    let methods = match &options.factory {
+        Some(name) => factory::factory(
+            name,
+            &closure.body,
+            &solve_for,
+            &result.body,
+            &target_ident,
+            ty,
+        )?,
+        // Also an inherent method forwarding to the trait, so callers need not import it. It is as
+        // visible as the type, like the trait's method.
+        None if invertible => {
+            let vis = &ast.vis;
+            quote!(
+                #[allow(dead_code)]
+                #vis fn #calculate_ident(#input: #ty) -> #ty {
+                    <Self as ::proc_lineq::Invertible>::calculate(#input)
+                }
+                #calculate_checked
+                #calculate_steps
+            )
+        }
+        None => quote!(
+            #calculate
+            #calculate_checked
+            #calculate_steps
+        ),
+    };
+    // The type's own generics, with extra_bounds added to its where-clause
+    let mut generics = ast.generics.clone();
+    if !options.extra_bounds.is_empty() {
+        generics
+            .make_where_clause()
+            .predicates
+            .extend(options.extra_bounds.iter().cloned());
+    }
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let invertible_impl = invertible.then(|| {
+        quote!(
+            #cfg
+            impl #impl_generics ::proc_lineq::Invertible for #struct_ident #ty_generics #where_clause {
+                type Num = #ty;
+
+                #calculate
+            }
+        )
+    });
+    Ok(quote!(
+    #cfg
+    impl #impl_generics #struct_ident #ty_generics #where_clause {
+        #methods
+        #forward_fn
+        #strategy
+        #bijection
+        #ops
+    }
+    #invertible_impl
+    #roundtrip_test
+    #verification
+    #overflow_warning))
+}
+
+/// Returns true if the expression applies unary negation anywhere.
+fn negates(e: &Expr) -> bool {
+    match e {
+        Expr::Unary(u) => matches!(u.op, UnOp::Neg(_)) || negates(&u.expr),
+        Expr::Binary(b) => negates(&b.left) || negates(&b.right),
+        Expr::Paren(p) => negates(&p.expr),
+        Expr::Cast(c) => negates(&c.expr),
+        _ => false,
+    }
+}
+
+/// Fails on a `.pow()` in a formula whose type is not a primitive integer, as the inverse is the
+/// exact integer root, found with `checked_pow`.
+fn require_integer_pow(e: &Expr, ty: &syn::Type) -> syn::Result<()> {
+    struct FindPow(Option<Span>);
+    impl<'ast> Visit<'ast> for FindPow {
+        fn visit_expr_method_call(&mut self, m: &'ast syn::ExprMethodCall) {
+            if m.method == "pow" && self.0.is_none() {
+                self.0 = Some(m.method.span());
+            }
+            syn::visit::visit_expr_method_call(self, m);
+        }
+    }
+    let mut find = FindPow(None);
+    find.visit_expr(e);
+    match find.0 {
+        Some(span) if !literals::is_integer(ty) => Err(syn::Error::new(
+            span,
+            format!(
+                "`.pow()` is inverted as an exact integer root, which requires a primitive \
+                 integer type, but the formula's type is `{}`",
+                quote!(#ty)
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Parses a formula written either as a closure, `|| a + 2`, or as the bare expression `a + 2`.
+///
+/// An equation with the closure's parameter alone on one side, `|y| a + 2 == y`, is taken as
+/// the formula on the other side.
+fn parse_formula(formula: &LitStr) -> syn::Result<ExprClosure> {
+    let closure = match unicode::normalize(formula).parse::<Expr>() {
+        Ok(Expr::Closure(closure)) => closure,
+        Ok(expr) => syn::parse_quote!(|| #expr),
+        Err(e) => {
+            return Err(syn::Error::new(
+                formula.span(),
+                format!("could not parse formula: {}", e),
+            ))
+        }
+    };
+    match &*closure.body {
+        Expr::Binary(b) if matches!(b.op, syn::BinOp::Eq(_)) => {
+            let output = match closure_params(&closure)?.as_slice() {
+                [output] => output.clone(),
+                _ => {
+                    return Err(syn::Error::new(
+                        formula.span(),
+                        "an equation takes a single parameter for the value it equals",
+                    ))
+                }
+            };
+            let is_output = |e: &Expr| matches!(e, Expr::Path(p) if p.path.is_ident(&output));
+            let formula_side = match (is_output(&b.left), is_output(&b.right)) {
+                (false, true) => &b.left,
+                (true, false) => &b.right,
+                _ => {
+                    return Err(syn::Error::new(
+                        formula.span(),
+                        format!("an equation must have `{}` alone on one side", output),
+                    ))
+                }
+            };
+            Ok(syn::parse_quote!(|| #formula_side))
+        }
+        _ => Ok(closure),
+    }
+}
+
+/// Returns the identifiers bound by a closure's parameters.
+fn closure_params(closure: &ExprClosure) -> syn::Result<Vec<Ident>> {
+    closure
+        .inputs
+        .iter()
+        .map(|input| match input {
+            Pat::Ident(p) => Ok(p.ident.clone()),
+            Pat::Type(PatType { pat, .. }) => match &**pat {
+                Pat::Ident(p) => Ok(p.ident.clone()),
+                pat => Err(syn::Error::new_spanned(pat, "expected a parameter name")),
+            },
+            pat => Err(syn::Error::new_spanned(pat, "expected a parameter name")),
+        })
+        .collect()
+}
+
+/// Returns `solve_for` followed by the other lower-case variables of a formula in the order they
+/// are first used, leaving out the names of `let` bindings. Upper-case names are constants, so a
+/// lower-case constant is taken as a parameter too.
+fn free_params(body: &Expr, solve_for: &Ident, lets: &[bindings::LetBinding]) -> Vec<Ident> {
+    struct Collect<'a> {
+        params: Vec<Ident>,
+        lets: &'a [bindings::LetBinding],
+    }
+    impl<'ast> Visit<'ast> for Collect<'_> {
+        fn visit_expr_path(&mut self, p: &'ast syn::ExprPath) {
+            let Some(ident) = p.path.get_ident() else {
+                return;
+            };
+            let lower = ident.to_string().starts_with(char::is_lowercase);
+            if lower
+                && !self.params.contains(ident)
+                && !self.lets.iter().any(|binding| binding.name == *ident)
+            {
+                self.params.push(ident.clone());
+            }
+        }
+    }
+    let mut collect = Collect {
+        params: vec![solve_for.clone()],
+        lets,
+    };
+    collect.visit_expr(body);
+    collect.params
+}
+
+/// Applies options to every `#[invert]` attribute among the items following them, unless the
+/// attribute sets the same option itself. The options end at a `;`.
+///
+/// The options are added to the attributes of structs and enums, including those in inline
+/// modules, and to nothing outside the macro.
+///
+/// ```
+/// use proc_lineq::Invertible;
+/// use proc_lineq_derive::ClosureInverter;
+///
+/// proc_lineq_derive::invert_defaults! {
+///     ty = "i64";
+///
+///     #[derive(ClosureInverter)]
+///     #[invert("|| a + 2")]
+///     struct Test;
+/// }
+///
+/// assert_eq!(Test::calculate(-5), -7);
+/// ```
+#[proc_macro]
+pub fn invert_defaults(tokens: TokenStream) -> TokenStream {
+    let Defaults { pairs, mut items } = parse_macro_input!(tokens as Defaults);
+    if let Some((key, _)) = pairs
+        .0
+        .iter()
+        .find(|(key, _)| key == "expr" || key == "pipeline")
+    {
+        return syn::Error::new(key.span(), "defaults cannot include a formula")
+            .to_compile_error()
+            .into();
+    }
+    // Check the defaults are valid options with a placeholder formula
+    let options = pairs.0.iter().map(|(key, value)| quote!(#key = #value));
+    if let Err(e) = parse2::<InvertOptions>(quote!("|| a", #(#options),*)) {
+        return e.to_compile_error().into();
+    }
+    for item in &mut items {
+        if let Err(e) = defaults::apply(item, &pairs) {
+            return e.to_compile_error().into();
+        }
+    }
+    quote!(#(#items)*).into()
+}
+
+/// Fails to compile unless the second closure is the inverse of the first, solving for `a`.
+///
+/// ```
+/// proc_lineq_derive::assert_inverses!(|| a * 2 + 1, |x| (x - 1) / 2);
+/// ```
+#[proc_macro]
+pub fn assert_inverses(tokens: TokenStream) -> TokenStream {
+    let pair = parse_macro_input!(tokens as InversePair);
+    let eq = ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+    match eq.is_inverse(&pair.forward, &pair.inverse) {
+        Ok(true) => TokenStream::new(),
+        Ok(false) => {
+            syn::Error::new_spanned(&pair.inverse, "closure is not the inverse of the formula")
+                .to_compile_error()
+                .into()
+        }
+        Err(e) => solve_error(&pair.forward, e).to_compile_error().into(),
+    }
+}
+
+/// Points an error in solving `closure` at the part of it the error was found in, or at the
+/// whole closure when the error has no span.
+fn solve_error(closure: &ExprClosure, e: proc_lineq::ParseError) -> syn::Error {
+    match e.span() {
+        Some(span) => syn::Error::new(span, e),
+        None => syn::Error::new_spanned(closure, e),
+    }
+}
+
+/// The arguments of `assert_inverses!`, a formula and its expected inverse.
+struct InversePair {
+    forward: ExprClosure,
+    inverse: ExprClosure,
+}
+
+impl Parse for InversePair {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let forward = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let inverse = input.parse()?;
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Self { forward, inverse })
+    }
+}
+
+/// Expands to an array of the inverse of a formula, solving for `a`, applied to each value of a
+/// range of integer literals.
+///
+/// ```
+/// const HALVES: [usize; 4] = proc_lineq_derive::invert_table!(|| a * 2, 0..4);
+/// assert_eq!(HALVES, [0, 0, 1, 1]);
+/// ```
+#[proc_macro]
+pub fn invert_table(tokens: TokenStream) -> TokenStream {
+    let table = parse_macro_input!(tokens as InverseTable);
+    let target_ident = format_ident!("b");
+    let eq = ClosureInverter::new(format_ident!("a"), target_ident.clone());
+    let inverse = match eq.solve(&table.closure) {
+        Ok(inverse) => inverse,
+        Err(e) => return solve_error(&table.closure, e).to_compile_error().into(),
+    };
+    let body = &inverse.body;
+    let values = table
+        .range
+        .iter()
+        .map(|i| proc_macro2::Literal::usize_unsuffixed(*i));
+    quote!({
+        const fn inverse(#target_ident: usize) -> usize {
+            #body
+        }
+        [#(inverse(#values)),*]
+    })
+    .into()
+}
+
+/// The arguments of `invert_table!`, a formula and the range of values to apply its inverse to.
+struct InverseTable {
+    closure: ExprClosure,
+    range: Vec<usize>,
+}
+
+impl Parse for InverseTable {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let closure = input.parse()?;
+        input.parse::<Token![,]>()?;
+        let range: ExprRange = input.parse()?;
+        let bound = |e: Option<&Expr>| -> syn::Result<usize> {
+            match e {
+                Some(Expr::Lit(ExprLit {
+                    lit: Lit::Int(i), ..
+                })) => i.base10_parse(),
+                _ => Err(syn::Error::new_spanned(
+                    &range,
+                    "expected a range between two integer literals",
+                )),
+            }
+        };
+        let start = bound(range.start.as_deref())?;
+        let end = bound(range.end.as_deref())?;
+        let range = match range.limits {
+            RangeLimits::HalfOpen(_) => (start..end).collect(),
+            RangeLimits::Closed(_) => (start..=end).collect(),
+        };
+        input.parse::<Option<Token![,]>>()?;
+        Ok(Self { closure, range })
+    }
+}
+
+/// Fills in the body of a function signature with the inverse of a formula, taking the
+/// function's single parameter as the value to invert.
+///
+/// ```
+/// #[proc_lineq_derive::invert("|| a * 3 + 2")]
+/// fn decode(value: u32) -> u32;
+///
+/// assert_eq!(decode(14), 4);
+/// ```
+///
+/// As the derive's helper attribute shares its name, apply it by its path rather than importing
+/// it alongside `ClosureInverter`.
+#[proc_macro_attribute]
+pub fn invert(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let formula = parse_macro_input!(attr as LitStr);
+    let InvertFn { attrs, vis, sig } = parse_macro_input!(item as InvertFn);
+    let (param, ty) = match sig.inputs.iter().collect::<Vec<_>>().as_slice() {
+        [syn::FnArg::Typed(PatType { pat, ty, .. })] => match &**pat {
+            Pat::Ident(p) => (p.ident.clone(), (**ty).clone()),
+            pat => {
+                return syn::Error::new_spanned(pat, "expected a parameter name")
+                    .to_compile_error()
+                    .into()
+            }
+        },
+        _ => {
+            return syn::Error::new_spanned(
+                &sig.inputs,
+                "expected a single parameter taking the value to invert",
+            )
+            .to_compile_error()
+            .into()
+        }
+    };
+    let mut closure = match parse_formula(&formula) {
+        Ok(closure) => closure,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    let solve_for = match ClosureInverter::bound_param(&closure) {
+        Ok(param) => param.unwrap_or_else(|| format_ident!("a")),
+        Err(e) => return solve_error(&closure, e).to_compile_error().into(),
+    };
+    if let [_, extra, ..] = free_params(&closure.body, &solve_for, &[]).as_slice() {
+        return syn::Error::new(
+            formula.span(),
+            format!("`{}` is not the function's parameter", extra),
+        )
+        .to_compile_error()
+        .into();
+    }
+    if let Err(e) = literals::normalize_literals(&mut closure.body, &ty, false) {
+        return e.to_compile_error().into();
+    }
+    if let Err(e) = require_integer_pow(&closure.body, &ty) {
+        return e.to_compile_error().into();
+    }
+    let eq = ClosureInverter::new(solve_for, param);
+    let mut result = match eq.solve(&closure) {
+        Ok(result) => result,
+        Err(e) => return solve_error(&closure, e).to_compile_error().into(),
+    };
+    if let Err(e) = markers::strip_const_exprs(&mut result.body) {
+        return e.to_compile_error().into();
+    }
+    if literals::is_unsigned(&ty) && negates(&result.body) {
+        return syn::Error::new(
+            formula.span(),
+            format!(
+                "the inverse negates a value, which the unsigned `{}` cannot represent",
+                quote!(#ty)
+            ),
+        )
+        .to_compile_error()
+        .into();
+    }
+    let body = &result.body;
+    quote!(
+        #(#attrs)*
+        #vis #sig {
+            #body
+        }
+    )
+    .into()
+}
+
+/// A function signature for `#[invert]` to fill in, without a body.
+struct InvertFn {
+    attrs: Vec<syn::Attribute>,
+    vis: syn::Visibility,
+    sig: syn::Signature,
+}
+
+impl Parse for InvertFn {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(syn::Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let sig = input.parse()?;
+        input.parse::<Token![;]>()?;
+        Ok(Self { attrs, vis, sig })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::{parse_quote, ImplItem, ImplItemFn, Item, Meta};
+
+    /// Returns the methods in the expansion of `options` on `Test`.
+    fn methods(options: InvertOptions) -> Vec<ImplItemFn> {
+        let ast: DeriveInput = parse_quote!(
+            struct Test;
+        );
+        let file: syn::File = parse2(expand(&ast, &options).unwrap()).unwrap();
+        file.items
+            .into_iter()
+            .filter_map(|item| match item {
+                Item::Impl(i) => Some(i),
+                _ => None,
+            })
+            .flat_map(|i| i.items)
+            .filter_map(|item| match item {
+                ImplItem::Fn(f) => Some(f),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the doc comment lines of the methods in the expansion of `options` on `Test`.
+    fn method_docs(options: InvertOptions) -> Vec<String> {
+        methods(options)
+            .iter()
+            .flat_map(|f| &f.attrs)
+            .filter_map(|attr| match &attr.meta {
+                Meta::NameValue(nv) if nv.path.is_ident("doc") => match &nv.value {
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(s), ..
+                    }) => Some(s.value()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn expand_doc_samples() {
+        let docs = method_docs(parse_quote!(
+            "|| a * 2 + 1",
+            samples = [(7, 3), (11, 5)],
+            gen_doctest = true
+        ));
+        // `Test` is private, so the example cannot be run from outside the crate
+        assert_eq!(
+            docs,
+            [
+                " # Examples",
+                "",
+                " ```ignore",
+                " assert_eq!(Test::calculate(7), 3);",
+                " assert_eq!(Test::calculate(11), 5);",
+                " ```",
+            ]
+        );
+
+        // Nothing is documented without gen_doctest
+        let docs = method_docs(parse_quote!("|| a * 2 + 1", samples = [(7, 3)]));
+        assert!(docs.is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "log")]
+    fn expand_trace_calls() {
+        let ast: DeriveInput = parse_quote!(
+            struct Test;
+        );
+        let options: InvertOptions = parse_quote!("|| a * 3 + 1", trace_calls = true);
+        let expanded = expand(&ast, &options).unwrap().to_string();
+        // From the root, so neither a renamed `log` nor a local module named `log` is used
+        assert!(expanded.contains(
+            ":: log :: trace ! (\"{}({}) = {}\" , stringify ! (calculate) , value , result)"
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "proptest")]
+    fn expand_strategy() {
+        let strategy = methods(parse_quote!(
+            expr = "|| 120 / a",
+            domain = "..=40",
+            nonzero_input = true,
+            gen_strategy = "input_strategy"
+        ))
+        .into_iter()
+        .find(|f| f.sig.ident == "input_strategy")
+        .unwrap();
+        let expected: ImplItemFn = parse_quote!(
+            fn input_strategy() -> impl ::proptest::strategy::Strategy<Value = usize> {
+                let lower: usize = <usize>::MIN;
+                let lower = lower.max(1);
+                let upper: usize = 40;
+                lower..=upper
+            }
+        );
+        assert_eq!(strategy, expected);
+    }
+
+    #[test]
+    fn expand_require_affine() {
+        let ast: DeriveInput = parse_quote!(
+            struct Test;
+        );
+        let error = expand(
+            &ast,
+            &parse_quote!(expr = "|| a * a + a", require_affine = true),
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "formula is degree 2 in `a`");
+        assert!(expand(
+            &ast,
+            &parse_quote!(expr = "|| a * 2 + 1", require_affine = true)
+        )
+        .is_ok());
     }
 }
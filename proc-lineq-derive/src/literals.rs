@@ -0,0 +1,223 @@
+use syn::visit::Visit;
+use syn::visit_mut::{self, VisitMut};
+use syn::{Expr, ExprLit, Lit, LitFloat, LitInt, Type};
+
+/// Returns true if the type is one of the primitive integer types.
+pub(crate) fn is_integer(ty: &Type) -> bool {
+    match ty {
+        Type::Path(p) => p.path.get_ident().is_some_and(|ident| {
+            matches!(
+                ident.to_string().as_str(),
+                "u8" | "u16"
+                    | "u32"
+                    | "u64"
+                    | "u128"
+                    | "usize"
+                    | "i8"
+                    | "i16"
+                    | "i32"
+                    | "i64"
+                    | "i128"
+                    | "isize"
+            )
+        }),
+        _ => false,
+    }
+}
+
+/// Returns true if the type is one of the primitive unsigned integer types.
+pub(crate) fn is_unsigned(ty: &Type) -> bool {
+    match ty {
+        Type::Path(p) => p.path.get_ident().is_some_and(|ident| {
+            matches!(
+                ident.to_string().as_str(),
+                "u8" | "u16" | "u32" | "u64" | "u128" | "usize"
+            )
+        }),
+        _ => false,
+    }
+}
+
+/// Returns true if the type is `f32` or `f64`.
+pub(crate) fn is_float(ty: &Type) -> bool {
+    match ty {
+        Type::Path(p) => p
+            .path
+            .get_ident()
+            .is_some_and(|ident| ident == "f32" || ident == "f64"),
+        _ => false,
+    }
+}
+
+/// Returns true if the formula has a float literal, such as the `1.8` of `a * 1.8 + 32`.
+pub(crate) fn contains_float(expr: &Expr) -> bool {
+    struct Find(bool);
+    impl Visit<'_> for Find {
+        fn visit_lit_float(&mut self, _: &LitFloat) {
+            self.0 = true;
+        }
+    }
+    let mut find = Find(false);
+    find.visit_expr(expr);
+    find.0
+}
+
+/// Returns the type named by the first suffixed literal of a formula, as `u32` for the `2u32`
+/// of `a * 2u32`, leaving the arguments of method calls such as `pow(2u32)`.
+pub(crate) fn suffix_type(expr: &Expr) -> Option<Type> {
+    struct Find(Option<Type>);
+    impl Visit<'_> for Find {
+        fn visit_expr(&mut self, e: &Expr) {
+            if self.0.is_some() {
+                return;
+            }
+            match e {
+                Expr::Lit(ExprLit { lit, .. }) => {
+                    self.0 = suffix(lit).and_then(|suffix| syn::parse_str(suffix).ok())
+                }
+                Expr::MethodCall(m) => self.visit_expr(&m.receiver),
+                _ => syn::visit::visit_expr(self, e),
+            }
+        }
+    }
+    let mut find = Find(None);
+    find.visit_expr(expr);
+    find.0
+}
+
+fn suffix(lit: &Lit) -> Option<&str> {
+    let suffix = match lit {
+        Lit::Int(i) => i.suffix(),
+        Lit::Float(f) => f.suffix(),
+        _ => return None,
+    };
+    (!suffix.is_empty()).then_some(suffix)
+}
+
+/// Rejects a suffixed literal whose type is not the output type, such as the `2u8` of
+/// `a * 2u8` with type `u32`, which would otherwise fail to compile inside the generated code.
+fn check_suffixes(expr: &Expr, ty: &Type) -> syn::Result<()> {
+    struct Check<'a> {
+        ty: &'a Type,
+        error: Option<syn::Error>,
+    }
+    impl Visit<'_> for Check<'_> {
+        fn visit_expr(&mut self, e: &Expr) {
+            match e {
+                // Float literals with an integer type are rejected or truncated separately
+                Expr::Lit(ExprLit {
+                    lit: Lit::Float(_), ..
+                }) if is_integer(self.ty) => {}
+                Expr::Lit(ExprLit { lit, .. }) if self.error.is_none() => {
+                    let ty = self.ty;
+                    match suffix(lit) {
+                        Some(suffix) if quote::quote!(#ty).to_string() != suffix => {
+                            self.error = Some(syn::Error::new(
+                                lit.span(),
+                                format!(
+                                    "literal {} has type {}, but the formula's type is {}",
+                                    quote::quote!(#lit),
+                                    suffix,
+                                    quote::quote!(#ty)
+                                ),
+                            ));
+                        }
+                        _ => {}
+                    }
+                }
+                Expr::MethodCall(m) => self.visit_expr(&m.receiver),
+                _ => syn::visit::visit_expr(self, e),
+            }
+        }
+    }
+    let mut check = Check { ty, error: None };
+    check.visit_expr(expr);
+    check.error.map_or(Ok(()), Err)
+}
+
+/// Checks the literals of a formula against its output type.
+///
+/// With an integer type, float literals are rejected, or truncated to integers when `coerce`
+/// is set. With a float type, unsuffixed integer literals are written as floats, so `32`
+/// becomes `32.0`. A suffixed literal must name the output type.
+pub(crate) fn normalize_literals(expr: &mut Expr, ty: &Type, coerce: bool) -> syn::Result<()> {
+    if is_integer(ty) || is_float(ty) {
+        check_suffixes(expr, ty)?;
+    }
+    if is_float(ty) {
+        Floats.visit_expr_mut(expr);
+        return Ok(());
+    }
+    if !is_integer(ty) {
+        return Ok(());
+    }
+    let mut normalize = Normalize {
+        ty,
+        coerce,
+        error: None,
+    };
+    normalize.visit_expr_mut(expr);
+    normalize.error.map_or(Ok(()), Err)
+}
+
+struct Normalize<'a> {
+    ty: &'a Type,
+    coerce: bool,
+    error: Option<syn::Error>,
+}
+
+impl VisitMut for Normalize<'_> {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        if let Expr::Lit(ExprLit {
+            lit: Lit::Float(f), ..
+        }) = e
+        {
+            if self.coerce {
+                match f.base10_parse::<f64>() {
+                    Ok(value) => {
+                        let truncated = LitInt::new(&format!("{}", value.trunc()), f.span());
+                        *e = Expr::Lit(ExprLit {
+                            attrs: vec![],
+                            lit: Lit::Int(truncated),
+                        });
+                    }
+                    Err(err) => self.error = Some(err),
+                }
+            } else if self.error.is_none() {
+                let ty = self.ty;
+                self.error = Some(syn::Error::new(
+                    f.span(),
+                    format!(
+                        "float literal {} with integer type {}",
+                        f.base10_digits(),
+                        quote::quote!(#ty)
+                    ),
+                ));
+            }
+            return;
+        }
+        visit_mut::visit_expr_mut(self, e);
+    }
+}
+
+/// Writes unsuffixed integer literals as floats, leaving the arguments of method calls such as
+/// `powi(2)`, which take integers.
+struct Floats;
+
+impl VisitMut for Floats {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        match e {
+            Expr::Lit(ExprLit {
+                lit: Lit::Int(i), ..
+            }) if i.suffix().is_empty() => {
+                let float = LitFloat::new(&format!("{}.0", i.base10_digits()), i.span());
+                *e = Expr::Lit(ExprLit {
+                    attrs: vec![],
+                    lit: Lit::Float(float),
+                });
+            }
+            Expr::MethodCall(m) => self.visit_expr_mut(&mut m.receiver),
+            _ => visit_mut::visit_expr_mut(self, e),
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use syn::visit_mut::{self, VisitMut};
+use syn::{Expr, ExprParen};
+
+/// Replaces each `const_expr!(...)` marker with its contents in parentheses.
+///
+/// The inverter treats the marker as an opaque constant, so it is only removed once solved.
+pub(crate) fn strip_const_exprs(expr: &mut Expr) -> syn::Result<()> {
+    let mut strip = Strip { error: None };
+    strip.visit_expr_mut(expr);
+    strip.error.map_or(Ok(()), Err)
+}
+
+struct Strip {
+    error: Option<syn::Error>,
+}
+
+impl VisitMut for Strip {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        if let Expr::Macro(m) = e {
+            if m.mac.path.is_ident("const_expr") {
+                match m.mac.parse_body::<Expr>() {
+                    Ok(inner) => {
+                        *e = Expr::Paren(ExprParen {
+                            attrs: vec![],
+                            paren_token: Default::default(),
+                            expr: Box::new(inner),
+                        })
+                    }
+                    Err(err) => self.error = Some(err),
+                }
+                return;
+            }
+        }
+        visit_mut::visit_expr_mut(self, e);
+    }
+}
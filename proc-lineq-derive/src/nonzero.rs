@@ -0,0 +1,46 @@
+use proc_macro2::Ident;
+use syn::{parse_quote, BinOp, Expr, Type};
+
+/// Returns the `core::num` non-zero counterpart of a primitive integer type.
+pub(crate) fn nonzero_type(ty: &Type) -> Option<Type> {
+    let Type::Path(p) = ty else {
+        return None;
+    };
+    let nonzero: Type = match p.path.get_ident()?.to_string().as_str() {
+        "u8" => parse_quote!(::core::num::NonZeroU8),
+        "u16" => parse_quote!(::core::num::NonZeroU16),
+        "u32" => parse_quote!(::core::num::NonZeroU32),
+        "u64" => parse_quote!(::core::num::NonZeroU64),
+        "u128" => parse_quote!(::core::num::NonZeroU128),
+        "usize" => parse_quote!(::core::num::NonZeroUsize),
+        "i8" => parse_quote!(::core::num::NonZeroI8),
+        "i16" => parse_quote!(::core::num::NonZeroI16),
+        "i32" => parse_quote!(::core::num::NonZeroI32),
+        "i64" => parse_quote!(::core::num::NonZeroI64),
+        "i128" => parse_quote!(::core::num::NonZeroI128),
+        "isize" => parse_quote!(::core::num::NonZeroIsize),
+        _ => return None,
+    };
+    Some(nonzero)
+}
+
+/// Returns true if the inverse divides by `ident` itself, so a zero input would divide by zero.
+pub(crate) fn divides_by(e: &Expr, ident: &Ident) -> bool {
+    match e {
+        Expr::Binary(b) => {
+            (matches!(b.op, BinOp::Div(_)) && is_ident(&b.right, ident))
+                || divides_by(&b.left, ident)
+                || divides_by(&b.right, ident)
+        }
+        Expr::Paren(p) => divides_by(&p.expr, ident),
+        _ => false,
+    }
+}
+
+fn is_ident(e: &Expr, ident: &Ident) -> bool {
+    match e {
+        Expr::Path(p) => p.path.is_ident(ident),
+        Expr::Paren(p) => is_ident(&p.expr, ident),
+        _ => false,
+    }
+}
@@ -0,0 +1,47 @@
+use crate::literals::is_unsigned;
+use syn::spanned::Spanned;
+use syn::visit_mut::{self, VisitMut};
+use syn::{parse_quote, BinOp, Expr, ExprLit, Lit, LitInt, Type};
+
+/// Lowers each division by a power of two into a right shift, which for unsigned types gives the
+/// same truncated result. Other types are left unchanged.
+pub(crate) fn shift_divisions(expr: &mut Expr, ty: &Type) {
+    if !is_unsigned(ty) {
+        return;
+    }
+    ShiftDivisions.visit_expr_mut(expr);
+    // Shifts bind more loosely than arithmetic so are grouped, which is unneeded at the top
+    if let Expr::Paren(p) = expr {
+        if matches!(&*p.expr, Expr::Binary(b) if matches!(b.op, BinOp::Shr(_))) {
+            *expr = (*p.expr).clone();
+        }
+    }
+}
+
+struct ShiftDivisions;
+
+impl VisitMut for ShiftDivisions {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        visit_mut::visit_expr_mut(self, e);
+        let Expr::Binary(b) = e else {
+            return;
+        };
+        let Expr::Lit(ExprLit {
+            lit: Lit::Int(divisor),
+            ..
+        }) = &*b.right
+        else {
+            return;
+        };
+        match divisor.base10_parse::<u128>() {
+            Ok(divisor)
+                if matches!(b.op, BinOp::Div(_)) && divisor > 1 && divisor.is_power_of_two() =>
+            {
+                let shift = LitInt::new(&divisor.trailing_zeros().to_string(), b.right.span());
+                let left = &b.left;
+                *e = parse_quote!((#left >> #shift));
+            }
+            _ => {}
+        }
+    }
+}
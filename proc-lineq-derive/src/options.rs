@@ -0,0 +1,599 @@
+use crate::bindings::LetBinding;
+use crate::domain::Domain;
+use crate::literals;
+use crate::samples::{self, Sample};
+use proc_macro2::Span;
+use quote::format_ident;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Expr, ExprLit, Ident, Lit, LitBool, LitStr, Meta, Path, Token, Type, WherePredicate};
+
+/// The closure or closures to invert.
+pub(crate) enum Formula {
+    Expr(LitStr),
+    /// Closures composed left to right before inverting
+    Pipeline(Vec<LitStr>),
+}
+
+/// Options parsed from a single `#[invert(...)]` attribute.
+///
+/// The formula is either given positionally, `#[invert("|| a + 2")]`, with the `expr` key,
+/// `#[invert(expr = "|| a + 2", debug_bounds = true)]`, or as a `pipeline` of closures.
+pub(crate) struct InvertOptions {
+    pub formula: Formula,
+    pub solve_for: Option<Ident>,
+    /// The type the formula acts on, otherwise the type of its first suffixed literal, `f64` for
+    /// a formula with a float literal and `usize` for one without
+    pub ty: Option<Type>,
+    pub coerce_literals: bool,
+    pub debug_bounds: bool,
+    pub domain: Option<Domain>,
+    pub saturate_domain: bool,
+    pub lets: Vec<LetBinding>,
+    pub gen_test: Option<Ident>,
+    pub trace_calls: bool,
+    pub variant: Option<Ident>,
+    /// A tuple struct wrapping the returned value
+    pub result_wrap: Option<Path>,
+    /// `uom` quantity types converted from and to, on whose underlying values the inverse acts
+    ///
+    /// Untested against the `uom` crate itself; the generated code assumes the public `value`,
+    /// `dimension` and `units` fields of `uom::si::Quantity`.
+    pub uom: Option<(Type, Type)>,
+    pub variants: Variants,
+    /// A predicate the generated items are gated on with `#[cfg(...)]`
+    pub cfg: Option<Meta>,
+    /// Takes the input as the non-zero counterpart of `ty`, for inverses dividing by it
+    pub nonzero_input: bool,
+    /// Generates `calculate_steps`, returning each intermediate value of the inverse, for inverses
+    /// made only of operators
+    pub steps: bool,
+    /// What to do when the inverse overflows for most values of `ty`
+    pub overflow_check: Option<OverflowCheck>,
+    /// Generates a function of this name taking the named constants of the inverse and
+    /// returning the inverse as a closure, in place of `calculate`
+    pub factory: Option<Ident>,
+    /// Lowers divisions by powers of two in the inverse into shifts, for unsigned types
+    pub optimize: bool,
+    /// Asserts the target shares no set bits with the constants it is or-ed with
+    pub disjoint: bool,
+    /// Takes and returns `Option`s, passing `None` through
+    pub option_input: bool,
+    /// Generates a function of this name returning a `proptest` strategy over valid inputs
+    pub gen_strategy: Option<Ident>,
+    /// Known inputs of `calculate` and the values it returns
+    pub samples: Vec<Sample>,
+    /// Documents `calculate` with an example asserting each of the samples. The example is only
+    /// run by `cargo test --doc` when `calculate` implements `Invertible` on a `pub` type
+    /// reachable at its module path, as doc tests are compiled outside the crate, and is marked
+    /// `ignore` otherwise
+    pub gen_doctest: bool,
+    /// A function the result of the inverse is passed through
+    pub post_fn: Option<Path>,
+    /// Generates the constant `BIJECTION` pairing the formula with its inverse
+    pub bijection_struct: bool,
+    /// Generates `forward`, evaluating the formula itself
+    pub forward: bool,
+    /// Asserts at compile time that the inverse recovers a range of small inputs
+    pub verify: bool,
+    /// Generates the constant `OPS` describing the inverse as a sequence of operations
+    pub emit_ir: bool,
+    /// Predicates added to the where-clause of the generated impl
+    pub extra_bounds: Vec<WherePredicate>,
+    /// `calculate` returns an error rather than truncating when a division is inexact
+    pub exact: bool,
+    /// `calculate` takes its input by reference, cloning it for types which are not `Copy`
+    pub deref_input: bool,
+    /// Rejects a formula of a degree above 1 in the target, reporting its degree
+    pub require_affine: bool,
+    /// The name of `calculate`, which `calculate_checked` and `calculate_steps` take as a prefix.
+    /// Given as `name` or `method`.
+    pub name: Option<Ident>,
+    /// Generates `calculate` as a `const fn`, for use in constant expressions
+    pub const_fn: bool,
+}
+
+/// How an inverse which overflows for most of its inputs is reported.
+pub(crate) enum OverflowCheck {
+    Warn,
+    Deny,
+}
+
+/// Which forms of `calculate` to generate from the one inverse.
+pub(crate) struct Variants {
+    /// `calculate`, evaluating the inverse directly
+    pub plain: bool,
+    /// `calculate_checked`, returning `None` when a step overflows or divides by zero
+    pub checked: bool,
+}
+
+impl Variants {
+    fn parse(value: &Expr) -> syn::Result<Self> {
+        let names = lit_strs(value)?;
+        if names.is_empty() {
+            return Err(syn::Error::new_spanned(
+                value,
+                "variants requires at least one of `plain` or `checked`",
+            ));
+        }
+        let mut variants = Self {
+            plain: false,
+            checked: false,
+        };
+        for name in names {
+            match name.value().as_str() {
+                "plain" => variants.plain = true,
+                "checked" => variants.checked = true,
+                _ => {
+                    return Err(syn::Error::new(
+                        name.span(),
+                        "unknown variant, expected `plain` or `checked`",
+                    ))
+                }
+            }
+        }
+        Ok(variants)
+    }
+}
+
+impl InvertOptions {
+    /// The name of `calculate`, or with a `suffix` of the method derived from it, such as
+    /// `calculate_checked`.
+    pub fn method(&self, suffix: &str) -> Ident {
+        let name = self
+            .name
+            .clone()
+            .unwrap_or_else(|| Ident::new("calculate", Span::call_site()));
+        if suffix.is_empty() {
+            name
+        } else {
+            format_ident!("{}_{}", name, suffix, span = name.span())
+        }
+    }
+
+    /// The methods and constants generated for the attribute, which must differ from those of
+    /// any other attribute on the same type.
+    pub fn item_names(&self) -> Vec<Ident> {
+        let mut items = match &self.factory {
+            Some(factory) => vec![factory.clone()],
+            None => {
+                let mut methods = vec![];
+                if self.variants.plain {
+                    methods.push(self.method(""));
+                }
+                if self.variants.checked {
+                    methods.push(self.method("checked"));
+                }
+                if self.steps {
+                    methods.push(self.method("steps"));
+                }
+                methods
+            }
+        };
+        items.extend(self.gen_strategy.clone());
+        if self.forward {
+            items.push(Ident::new("forward", Span::call_site()));
+        }
+        if self.bijection_struct {
+            items.push(Ident::new("BIJECTION", Span::call_site()));
+        }
+        if self.emit_ir {
+            items.push(Ident::new("OPS", Span::call_site()));
+        }
+        items
+    }
+}
+
+impl Parse for InvertOptions {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut expr = None;
+        let mut pipeline = None;
+        let mut solve_for = None;
+        let mut ty = None;
+        let mut coerce_literals = false;
+        let mut debug_bounds = false;
+        let mut domain = None;
+        let mut saturate_domain = None;
+        let mut lets = vec![];
+        let mut gen_test = None;
+        let mut trace_calls = false;
+        let mut variant = None;
+        let mut result_wrap = None;
+        let mut uom_in = None;
+        let mut uom_out = None;
+        let mut variants = None;
+        let mut cfg = None;
+        let mut nonzero_input = None;
+        let mut steps = false;
+        let mut overflow_check = None;
+        let mut factory = None;
+        let mut optimize = false;
+        let mut disjoint = false;
+        let mut option_input = None;
+        let mut gen_strategy = None;
+        let mut samples = None;
+        let mut gen_doctest = None;
+        let mut post_fn = None;
+        let mut bijection_struct = false;
+        let mut forward = false;
+        let mut verify = false;
+        let mut emit_ir = false;
+        let mut extra_bounds = None;
+        let mut exact = None;
+        let mut deref_input = None;
+        let mut require_affine = true;
+        let mut name = None;
+        let mut const_fn = None;
+
+        if input.peek(LitStr) {
+            expr = Some(input.parse::<LitStr>()?);
+            if !input.is_empty() {
+                input.parse::<Token![,]>()?;
+            }
+        }
+
+        let pairs = input.parse::<OptionPairs>()?.0;
+
+        for (key, value) in pairs {
+            match key.to_string().as_str() {
+                "expr" => set_once(&mut expr, &key, lit_str(&value)?)?,
+                "pipeline" => set_once(&mut pipeline, &key, (key.span(), lit_strs(&value)?))?,
+                "solve_for" => set_once(&mut solve_for, &key, lit_str(&value)?.parse::<Ident>()?)?,
+                "ty" => set_once(&mut ty, &key, lit_str(&value)?.parse::<Type>()?)?,
+                "coerce_literals" => coerce_literals = lit_bool(&value)?.value,
+                "debug_bounds" => debug_bounds = lit_bool(&value)?.value,
+                "domain" => set_once(&mut domain, &key, Domain::parse(&lit_str(&value)?)?)?,
+                "saturate_domain" => saturate_domain = Some(lit_bool(&value)?),
+                "let" => lets.push(lit_str(&value)?.parse::<LetBinding>()?),
+                "trace_calls" => {
+                    let trace = lit_bool(&value)?;
+                    if trace.value && !cfg!(feature = "log") {
+                        return Err(syn::Error::new(
+                            trace.span,
+                            "trace_calls requires the `log` feature of proc-lineq-derive",
+                        ));
+                    }
+                    trace_calls = trace.value;
+                }
+                "steps" => {
+                    let enabled = lit_bool(&value)?;
+                    if enabled.value && !cfg!(feature = "std") {
+                        return Err(syn::Error::new(
+                            enabled.span,
+                            "steps requires the `std` feature of proc-lineq-derive",
+                        ));
+                    }
+                    steps = enabled.value;
+                }
+                "overflow_check" => {
+                    let check = lit_str(&value)?;
+                    let check = match check.value().as_str() {
+                        "warn" => OverflowCheck::Warn,
+                        "deny" => OverflowCheck::Deny,
+                        _ => {
+                            return Err(syn::Error::new(check.span(), "expected `warn` or `deny`"))
+                        }
+                    };
+                    set_once(&mut overflow_check, &key, check)?
+                }
+                "factory" => set_once(&mut factory, &key, lit_str(&value)?.parse::<Ident>()?)?,
+                "optimize" => optimize = lit_bool(&value)?.value,
+                "disjoint" => disjoint = lit_bool(&value)?.value,
+                "option_input" => option_input = Some(lit_bool(&value)?),
+                "gen_strategy" if !cfg!(feature = "proptest") => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "gen_strategy requires the `proptest` feature of proc-lineq-derive",
+                    ));
+                }
+                "gen_strategy" => {
+                    set_once(&mut gen_strategy, &key, lit_str(&value)?.parse::<Ident>()?)?
+                }
+                "samples" => set_once(&mut samples, &key, samples::parse(&value)?)?,
+                "gen_doctest" => gen_doctest = Some(lit_bool(&value)?),
+                "post_fn" => set_once(&mut post_fn, &key, lit_str(&value)?.parse::<Path>()?)?,
+                "bijection_struct" => bijection_struct = lit_bool(&value)?.value,
+                "forward" => forward = lit_bool(&value)?.value,
+                "verify" => verify = lit_bool(&value)?.value,
+                "emit_ir" => emit_ir = lit_bool(&value)?.value,
+                "exact" => exact = Some(lit_bool(&value)?),
+                "deref_input" => deref_input = Some(lit_bool(&value)?),
+                "require_affine" => require_affine = lit_bool(&value)?.value,
+                "name" => set_once(&mut name, &key, lit_str(&value)?.parse::<Ident>()?)?,
+                "const_fn" => const_fn = Some(lit_bool(&value)?),
+                "extra_bounds" => set_once(
+                    &mut extra_bounds,
+                    &key,
+                    lit_str(&value)?
+                        .parse_with(Punctuated::<WherePredicate, Token![,]>::parse_terminated)?,
+                )?,
+                "variant" => set_once(&mut variant, &key, lit_str(&value)?.parse::<Ident>()?)?,
+                "result_wrap" => {
+                    let wrap = lit_str(&value)?.parse::<Path>()?;
+                    // The path is called to wrap the result, so it cannot take generic arguments
+                    if wrap
+                        .segments
+                        .iter()
+                        .any(|segment| !segment.arguments.is_none())
+                    {
+                        return Err(syn::Error::new_spanned(
+                            &value,
+                            "result_wrap must name a tuple struct with a single field, such as `Meters`, without generic arguments",
+                        ));
+                    }
+                    set_once(&mut result_wrap, &key, wrap)?
+                }
+                "uom_in" | "uom_out" if !cfg!(feature = "uom") => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "uom options require the `uom` feature of proc-lineq-derive",
+                    ));
+                }
+                "uom_in" => set_once(&mut uom_in, &key, lit_str(&value)?.parse::<Type>()?)?,
+                "uom_out" => set_once(&mut uom_out, &key, lit_str(&value)?.parse::<Type>()?)?,
+                "variants" => set_once(&mut variants, &key, Variants::parse(&value)?)?,
+                "cfg" => set_once(&mut cfg, &key, lit_str(&value)?.parse::<Meta>()?)?,
+                "nonzero_input" => nonzero_input = Some(lit_bool(&value)?),
+                "gen_test" => set_once(&mut gen_test, &key, lit_str(&value)?.parse::<Ident>()?)?,
+                _ => return Err(syn::Error::new(key.span(), "unknown invert option")),
+            }
+        }
+
+        let formula = match (expr, pipeline) {
+            (Some(expr), None) => Formula::Expr(expr),
+            (None, Some((span, stages))) if stages.is_empty() => {
+                return Err(syn::Error::new(
+                    span,
+                    "pipeline requires at least one closure",
+                ));
+            }
+            (None, Some((_, stages))) => Formula::Pipeline(stages),
+            (Some(expr), Some(_)) => {
+                return Err(syn::Error::new(
+                    expr.span(),
+                    "invert takes either a formula or a pipeline, not both",
+                ));
+            }
+            (None, None) => {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "invert requires a formula string",
+                ));
+            }
+        };
+
+        if let Some(saturate) = &saturate_domain {
+            if saturate.value && domain.is_none() {
+                return Err(syn::Error::new(
+                    saturate.span,
+                    "saturate_domain requires a domain",
+                ));
+            }
+        }
+
+        let uom = match (uom_in, uom_out) {
+            (Some(uom_in), Some(uom_out)) => Some((uom_in, uom_out)),
+            (None, None) => None,
+            _ => {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    "uom_in and uom_out must be given together",
+                ))
+            }
+        };
+        if let (Some(_), Some(nonzero)) = (&uom, &nonzero_input) {
+            if nonzero.value {
+                return Err(syn::Error::new(
+                    nonzero.span,
+                    "nonzero_input cannot be combined with uom quantities",
+                ));
+            }
+        }
+        if let (Some(option), Some(_)) = (&option_input, &variant) {
+            if option.value {
+                return Err(syn::Error::new(
+                    option.span,
+                    "option_input cannot be combined with variant",
+                ));
+            }
+        }
+        if let Some(doctest) = gen_doctest.as_ref().filter(|g| g.value) {
+            if samples.is_none() {
+                return Err(syn::Error::new(
+                    doctest.span,
+                    "gen_doctest requires samples",
+                ));
+            }
+            let enabled = |option: &Option<LitBool>| option.as_ref().is_some_and(|o| o.value);
+            if uom.is_some()
+                || factory.is_some()
+                || enabled(&option_input)
+                || enabled(&nonzero_input)
+            {
+                return Err(syn::Error::new(
+                    doctest.span,
+                    "gen_doctest cannot be combined with uom, factory, option_input or nonzero_input",
+                ));
+            }
+        }
+        if let Some(factory) = &factory {
+            if gen_test.is_some()
+                || variant.is_some()
+                || variants.is_some()
+                || steps
+                || name.is_some()
+            {
+                return Err(syn::Error::new(
+                    factory.span(),
+                    "factory cannot be combined with gen_test, variant, variants, steps or name",
+                ));
+            }
+        }
+        if let Some(exact) = exact.as_ref().filter(|e| e.value) {
+            if variant.is_some()
+                || factory.is_some()
+                || gen_test.is_some()
+                || option_input.as_ref().is_some_and(|o| o.value)
+                || gen_doctest.as_ref().is_some_and(|g| g.value)
+            {
+                return Err(syn::Error::new(
+                    exact.span,
+                    "exact cannot be combined with variant, factory, gen_test, option_input or gen_doctest",
+                ));
+            }
+            // Only integer division leaves a remainder
+            if let Some(ty) = ty.as_ref().filter(|ty| !literals::is_integer(ty)) {
+                return Err(syn::Error::new_spanned(
+                    ty,
+                    "exact requires a primitive integer type",
+                ));
+            }
+        }
+        if let Some(deref) = deref_input.as_ref().filter(|d| d.value) {
+            if uom.is_some()
+                || variant.is_some()
+                || factory.is_some()
+                || gen_test.is_some()
+                || nonzero_input.as_ref().is_some_and(|n| n.value)
+                || gen_doctest.as_ref().is_some_and(|g| g.value)
+            {
+                return Err(syn::Error::new(
+                    deref.span,
+                    "deref_input cannot be combined with uom, variant, factory, gen_test, nonzero_input or gen_doctest",
+                ));
+            }
+        }
+        // Formatting, clamping and unwrapping are not available in a `const fn`
+        if let Some(const_fn) = const_fn.as_ref().filter(|c| c.value) {
+            if debug_bounds
+                || trace_calls
+                || factory.is_some()
+                || saturate_domain.as_ref().is_some_and(|s| s.value)
+                || option_input.as_ref().is_some_and(|o| o.value)
+                || deref_input.as_ref().is_some_and(|d| d.value)
+            {
+                return Err(syn::Error::new(
+                    const_fn.span,
+                    "const_fn cannot be combined with debug_bounds, trace_calls, factory, saturate_domain, option_input or deref_input",
+                ));
+            }
+        }
+        if let (Some(_), Some(wrap)) = (&uom, &result_wrap) {
+            return Err(syn::Error::new_spanned(
+                wrap,
+                "result_wrap cannot be combined with uom quantities",
+            ));
+        }
+
+        Ok(Self {
+            formula,
+            solve_for,
+            ty,
+            coerce_literals,
+            debug_bounds,
+            domain,
+            saturate_domain: saturate_domain.is_some_and(|s| s.value),
+            lets,
+            gen_test,
+            trace_calls,
+            variant,
+            result_wrap,
+            uom,
+            variants: variants.unwrap_or(Variants {
+                plain: true,
+                checked: false,
+            }),
+            cfg,
+            nonzero_input: nonzero_input.is_some_and(|n| n.value),
+            steps,
+            overflow_check,
+            factory,
+            optimize,
+            disjoint,
+            option_input: option_input.is_some_and(|o| o.value),
+            gen_strategy,
+            samples: samples.unwrap_or_default(),
+            gen_doctest: gen_doctest.is_some_and(|g| g.value),
+            post_fn,
+            bijection_struct,
+            forward,
+            verify,
+            emit_ir,
+            extra_bounds: extra_bounds.into_iter().flatten().collect(),
+            exact: exact.is_some_and(|e| e.value),
+            deref_input: deref_input.is_some_and(|d| d.value),
+            require_affine,
+            name,
+            const_fn: const_fn.is_some_and(|c| c.value),
+        })
+    }
+}
+
+/// A comma separated list of `key = value` options, ending at the end of the input or a `;`.
+pub(crate) struct OptionPairs(pub Vec<(Ident, Expr)>);
+
+impl Parse for OptionPairs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut pairs = vec![];
+        while !input.is_empty() && !input.peek(Token![;]) {
+            // `parse_any` so that keyword keys such as `type` and `let` are accepted
+            let key = Ident::parse_any(input)?;
+            // `type` is an alias of `ty` and `method` of `name`, so either overrides a default
+            // given with the other
+            let key = if key == "type" {
+                Ident::new("ty", key.span())
+            } else if key == "method" {
+                Ident::new("name", key.span())
+            } else {
+                key
+            };
+            input.parse::<Token![=]>()?;
+            pairs.push((key, input.parse::<Expr>()?));
+
+            if !input.is_empty() && !input.peek(Token![;]) {
+                input.parse::<Token![,]>()?;
+            }
+        }
+        Ok(Self(pairs))
+    }
+}
+
+fn set_once<T>(slot: &mut Option<T>, key: &Ident, value: T) -> syn::Result<()> {
+    if slot.is_some() {
+        return Err(syn::Error::new(
+            key.span(),
+            format!("duplicate `{}` option", key),
+        ));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+fn lit_str(value: &Expr) -> syn::Result<LitStr> {
+    match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(s), ..
+        }) => Ok(s.clone()),
+        _ => Err(syn::Error::new_spanned(value, "expected a string literal")),
+    }
+}
+
+fn lit_strs(value: &Expr) -> syn::Result<Vec<LitStr>> {
+    match value {
+        Expr::Array(array) => array.elems.iter().map(lit_str).collect(),
+        _ => Err(syn::Error::new_spanned(
+            value,
+            "expected an array of string literals",
+        )),
+    }
+}
+
+fn lit_bool(value: &Expr) -> syn::Result<LitBool> {
+    match value {
+        Expr::Lit(ExprLit {
+            lit: Lit::Bool(b), ..
+        }) => Ok(b.clone()),
+        _ => Err(syn::Error::new_spanned(value, "expected `true` or `false`")),
+    }
+}
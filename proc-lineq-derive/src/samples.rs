@@ -0,0 +1,147 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::{Expr, ExprLit, Lit, LitInt};
+
+/// A known input of `calculate` and the value it returns, from `samples = [(5, 3)]`.
+pub(crate) struct Sample {
+    pub input: LitInt,
+    pub output: LitInt,
+}
+
+/// Parses an array of `(input, output)` pairs of integer literals.
+pub(crate) fn parse(value: &Expr) -> syn::Result<Vec<Sample>> {
+    let Expr::Array(array) = value else {
+        return Err(syn::Error::new_spanned(
+            value,
+            "expected an array of `(input, output)` pairs",
+        ));
+    };
+    array
+        .elems
+        .iter()
+        .map(|elem| match elem {
+            Expr::Tuple(tuple) if tuple.elems.len() == 2 => Ok(Sample {
+                input: lit_int(&tuple.elems[0])?,
+                output: lit_int(&tuple.elems[1])?,
+            }),
+            _ => Err(syn::Error::new_spanned(
+                elem,
+                "expected an `(input, output)` pair",
+            )),
+        })
+        .collect()
+}
+
+fn lit_int(e: &Expr) -> syn::Result<LitInt> {
+    match e {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(i), ..
+        }) => Ok(i.clone()),
+        _ => Err(syn::Error::new_spanned(e, "expected an integer literal")),
+    }
+}
+
+/// Returns the `#[doc]` values of an example asserting `method`, such as `calculate`, gives each
+/// sample's output, with the result unwrapped from `result_wrap` when `wrapped` is set.
+///
+/// Doc tests are compiled outside the crate, so only a `runnable` example imports the struct
+/// from the module it is defined in. Any other example is marked `ignore`.
+pub(crate) fn doc_example(
+    struct_ident: &Ident,
+    method: &Ident,
+    runnable: bool,
+    variant: Option<&Ident>,
+    wrapped: bool,
+    samples: &[Sample],
+) -> Vec<TokenStream> {
+    let mut lines = vec![quote!(" # Examples"), quote!("")];
+    if runnable {
+        let import = format!("::{};", struct_ident);
+        lines.push(quote!(" ```"));
+        lines.push(quote!(concat!(" # use ", module_path!(), #import)));
+    } else {
+        lines.push(quote!(" ```ignore"));
+    }
+    let field = if wrapped { ".0" } else { "" };
+    for Sample { input, output } in samples {
+        let call = match variant {
+            Some(variant) => format!(
+                "{}::{}({}).{}().unwrap(){}",
+                struct_ident, variant, input, method, field
+            ),
+            None => format!("{}::{}({}){}", struct_ident, method, input, field),
+        };
+        let line = format!(" assert_eq!({}, {});", call, output);
+        lines.push(quote!(#line));
+    }
+    lines.push(quote!(" ```"));
+    lines
+}
+
+/// Returns the `#[doc]` attributes of the example built by [`doc_example`].
+pub(crate) fn doc_attrs(
+    struct_ident: &Ident,
+    method: &Ident,
+    runnable: bool,
+    variant: Option<&Ident>,
+    wrapped: bool,
+    samples: &[Sample],
+) -> TokenStream {
+    let lines = doc_example(struct_ident, method, runnable, variant, wrapped, samples);
+    quote!(#(#[doc = #lines])*)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::{format_ident, quote};
+    use syn::parse_quote;
+
+    #[test]
+    fn doc_example_asserts_samples() {
+        let samples = parse(&parse_quote!([(5, 3), (10, 5)])).unwrap();
+        let calculate = format_ident!("calculate");
+        let lines = doc_example(
+            &format_ident!("Test"),
+            &calculate,
+            true,
+            None,
+            false,
+            &samples,
+        );
+        let expected = [
+            quote!(" # Examples"),
+            quote!(""),
+            quote!(" ```"),
+            quote!(concat!(" # use ", module_path!(), "::Test;")),
+            quote!(" assert_eq!(Test::calculate(5), 3);"),
+            quote!(" assert_eq!(Test::calculate(10), 5);"),
+            quote!(" ```"),
+        ];
+        assert_eq!(
+            lines.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            expected.iter().map(ToString::to_string).collect::<Vec<_>>()
+        );
+
+        // Without imports, as the example is not compiled
+        let lines = doc_example(
+            &format_ident!("Length"),
+            &format_ident!("to_feet"),
+            false,
+            Some(&format_ident!("Feet")),
+            true,
+            &samples[..1],
+        );
+        let expected = [
+            quote!(" # Examples"),
+            quote!(""),
+            quote!(" ```ignore"),
+            quote!(" assert_eq!(Length::Feet(5).to_feet().unwrap().0, 3);"),
+            quote!(" ```"),
+        ];
+        assert_eq!(
+            lines.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            expected.iter().map(ToString::to_string).collect::<Vec<_>>()
+        );
+    }
+}
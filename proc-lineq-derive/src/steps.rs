@@ -0,0 +1,95 @@
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{quote, ToTokens};
+use syn::{Expr, Type, UnOp};
+
+/// Returns a `steps` method named `ident`, taking `params`, which runs `preamble` and then
+/// returns each intermediate value of the inverse applied to `input`.
+pub(crate) fn method(
+    ident: &Ident,
+    params: TokenStream,
+    preamble: TokenStream,
+    inverse: &Expr,
+    input: &Ident,
+    target: &Ident,
+    ty: &Type,
+) -> syn::Result<TokenStream> {
+    let steps = steps(inverse, target, ty)?;
+    Ok(quote!(
+        fn #ident(#params) -> ::std::vec::Vec<(::std::string::String, #ty)> {
+            #preamble
+            let #target = #input;
+            let mut steps = ::std::vec::Vec::new();
+            #steps
+            steps
+        }
+    ))
+}
+
+/// Lowers an inverse into statements pushing each intermediate value onto `steps`, labelled
+/// with the operation applied to the previous value, which is written as `x`.
+///
+/// Operations closest to the target come first, the order in which they are evaluated.
+fn steps(e: &Expr, target: &Ident, ty: &Type) -> syn::Result<TokenStream> {
+    let x = Ident::new("x", Span::mixed_site());
+    let mut ops = vec![];
+    collect(e, target, &x, &mut ops)?;
+    let steps = ops.iter().map(|(op, label)| {
+        quote!(
+            let #x: #ty = #op;
+            steps.push((::std::string::String::from(#label), #x));
+        )
+    });
+    Ok(quote!(
+        let #x: #ty = #target;
+        #(#steps)*
+    ))
+}
+
+/// Collects each operation on the path from the root down to the target, innermost first,
+/// failing on anything which is not a single operator.
+fn collect(
+    e: &Expr,
+    target: &Ident,
+    x: &Ident,
+    ops: &mut Vec<(TokenStream, String)>,
+) -> syn::Result<()> {
+    match e {
+        Expr::Binary(b) if contains(&b.left, target) => {
+            collect(&b.left, target, x, ops)?;
+            let (op, right) = (&b.op, &b.right);
+            let label = format!("{} {} {}", x, op.to_token_stream(), right.to_token_stream());
+            ops.push((quote!(#x #op #right), label));
+            Ok(())
+        }
+        Expr::Binary(b) if contains(&b.right, target) => {
+            collect(&b.right, target, x, ops)?;
+            let (left, op) = (&b.left, &b.op);
+            let label = format!("{} {} {}", left.to_token_stream(), op.to_token_stream(), x);
+            ops.push((quote!(#left #op #x), label));
+            Ok(())
+        }
+        Expr::Unary(u) if matches!(u.op, UnOp::Neg(_) | UnOp::Not(_)) => {
+            collect(&u.expr, target, x, ops)?;
+            let op = &u.op;
+            let label = format!("{}{}", op.to_token_stream(), x);
+            ops.push((quote!(#op #x), label));
+            Ok(())
+        }
+        Expr::Paren(p) => collect(&p.expr, target, x, ops),
+        Expr::Path(p) if p.path.is_ident(target) => Ok(()),
+        _ => Err(syn::Error::new_spanned(
+            e,
+            "steps only supports binary operators and negation in the inverse",
+        )),
+    }
+}
+
+fn contains(e: &Expr, target: &Ident) -> bool {
+    match e {
+        Expr::Binary(b) => contains(&b.left, target) || contains(&b.right, target),
+        Expr::Unary(u) => contains(&u.expr, target),
+        Expr::Paren(p) => contains(&p.expr, target),
+        Expr::Path(p) => p.path.is_ident(target),
+        _ => false,
+    }
+}
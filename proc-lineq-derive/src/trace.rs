@@ -0,0 +1,8 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+
+/// Returns a statement logging the input of `method` and the `result` it computed, at the
+/// `log` crate's trace level.
+pub(crate) fn trace_call(method: &Ident, input: &Ident) -> TokenStream {
+    quote!(::log::trace!("{}({}) = {}", stringify!(#method), #input, result);)
+}
@@ -0,0 +1,19 @@
+use syn::LitStr;
+
+/// Replaces the Unicode math operators and spaces common in copied formulas with their ASCII
+/// equivalents, so that `a × 2 − 1` parses as `a * 2 - 1`.
+pub(crate) fn normalize(formula: &LitStr) -> LitStr {
+    let normalized: String = formula
+        .value()
+        .chars()
+        .map(|c| match c {
+            '\u{2212}' | '\u{2013}' => '-',
+            '\u{00D7}' | '\u{22C5}' | '\u{2217}' => '*',
+            '\u{00F7}' | '\u{2215}' => '/',
+            '\u{FF0B}' => '+',
+            c if c.is_whitespace() => ' ',
+            c => c,
+        })
+        .collect();
+    LitStr::new(&normalized, formula.span())
+}
@@ -0,0 +1,63 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::{Data, Fields};
+
+/// Returns the variant whose wrapped value the inverse is applied to, which an enum must name
+/// and a struct must not.
+pub(crate) fn resolve(data: &Data, variant: Option<&Ident>) -> syn::Result<Option<Ident>> {
+    match (data, variant) {
+        (Data::Struct(_), None) => Ok(None),
+        (Data::Struct(_), Some(variant)) => Err(syn::Error::new(
+            variant.span(),
+            "variant can only be used when deriving on an enum",
+        )),
+        (Data::Enum(_), None) => Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "ClosureInverter on an enum requires a `variant` option naming the variant to invert",
+        )),
+        (Data::Enum(data), Some(variant)) => {
+            match data.variants.iter().find(|v| v.ident == *variant) {
+                Some(v) if matches!(&v.fields, Fields::Unnamed(f) if f.unnamed.len() == 1) => {
+                    Ok(Some(variant.clone()))
+                }
+                Some(_) => Err(syn::Error::new(
+                    variant.span(),
+                    "variant must wrap a single unnamed field",
+                )),
+                None => Err(syn::Error::new(variant.span(), "no such variant")),
+            }
+        }
+        (Data::Union(_), _) => Err(syn::Error::new(
+            proc_macro2::Span::call_site(),
+            "ClosureInverter cannot be derived for unions",
+        )),
+    }
+}
+
+/// Wraps `inner`, a function of the wrapped value named `method`, in a method of the same name
+/// on the enum, which returns `None` on any other variant. A `checked` function already
+/// returns an `Option`.
+pub(crate) fn method(
+    variant: &Ident,
+    method: &Ident,
+    constness: Option<TokenStream>,
+    output_ty: &TokenStream,
+    inner: TokenStream,
+    checked: bool,
+) -> TokenStream {
+    let call = if checked {
+        quote!(#method(*value))
+    } else {
+        quote!(::core::option::Option::Some(#method(*value)))
+    };
+    quote!(
+        #constness fn #method(&self) -> ::core::option::Option<#output_ty> {
+            #inner
+            #[allow(unreachable_patterns)]
+            match self {
+                Self::#variant(value) => #call,
+                _ => ::core::option::Option::None,
+            }
+        }
+    )
+}
@@ -0,0 +1,108 @@
+use crate::checked;
+use crate::literals;
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{quote, quote_spanned};
+use syn::visit::Visit;
+use syn::{Expr, Type};
+
+/// The largest input `verify` checks the inverse recovers.
+pub(crate) const SAMPLES: u8 = 16;
+
+/// Returns a constant evaluated at compile time, so a lossy inverse breaks the build. Inputs the
+/// formula overflows on are skipped.
+pub(crate) fn verification(
+    forward: &Expr,
+    inverse: &Expr,
+    solve_for: &Ident,
+    target: &Ident,
+    ty: &Type,
+    cfg: Option<&TokenStream>,
+    span: Span,
+) -> syn::Result<TokenStream> {
+    if !literals::is_integer(ty) {
+        return Err(syn::Error::new_spanned(
+            ty,
+            "verify requires a primitive integer type",
+        ));
+    }
+    let checked_forward = checked::checked_expr(forward, ty);
+    let checked_inverse = checked::checked_expr(inverse, ty);
+    let message = format!(
+        "the inverse of `{}` does not recover every input from 0 to {}",
+        quote!(#forward),
+        SAMPLES
+    );
+    let samples = proc_macro2::Literal::u8_unsuffixed(SAMPLES);
+    Ok(quote_spanned!(span=>
+        #cfg
+        const _: () = {
+            const fn forward(#solve_for: #ty) -> ::core::option::Option<#ty> {
+                #checked_forward
+            }
+            const fn inverse(#target: #ty) -> ::core::option::Option<#ty> {
+                #checked_inverse
+            }
+            let mut x: #ty = 0;
+            while x <= #samples {
+                if let ::core::option::Option::Some(y) = forward(x) {
+                    match inverse(y) {
+                        ::core::option::Option::Some(z) if z == x => {}
+                        _ => panic!(#message),
+                    }
+                }
+                x += 1;
+            }
+        };
+    ))
+}
+
+/// Returns a test checking that every output the formula produces over a sample range is
+/// recovered by `inverse`, a call on `x`. Inputs that truncate are never produced so are
+/// skipped.
+pub(crate) fn round_trip_test(
+    test_ident: &Ident,
+    forward: &Expr,
+    inverse: TokenStream,
+    solve_for: &Ident,
+    ty: &Type,
+    cfg: Option<&TokenStream>,
+) -> TokenStream {
+    let forward = checked::checked_expr(forward, ty);
+    quote!(
+        #[cfg(test)]
+        #cfg
+        #[test]
+        fn #test_ident() {
+            let forward = |#solve_for: #ty| #forward;
+            for #solve_for in 0..=100 {
+                if let ::core::option::Option::Some(x) = forward(#solve_for) {
+                    assert_eq!(
+                        forward(#inverse),
+                        ::core::option::Option::Some(x),
+                        "round trip failed for {}",
+                        x
+                    );
+                }
+            }
+        }
+    )
+}
+
+/// Returns the span of the first path in the expression starting with `Self`, which the checks
+/// above cannot refer to as they are generated outside the impl.
+pub(crate) fn self_path(e: &Expr) -> Option<Span> {
+    struct FindSelf(Option<Span>);
+    impl<'ast> Visit<'ast> for FindSelf {
+        fn visit_path(&mut self, p: &'ast syn::Path) {
+            match p.segments.first() {
+                Some(segment) if segment.ident == "Self" && self.0.is_none() => {
+                    self.0 = Some(segment.ident.span())
+                }
+                _ => syn::visit::visit_path(self, p),
+            }
+        }
+    }
+    let mut find = FindSelf(None);
+    find.visit_expr(e);
+    find.0
+}
@@ -0,0 +1,20 @@
+/// A formula and its inverse as a pair of functions, which can be stored and passed around.
+///
+/// Generated by `#[invert(bijection_struct = true)]` as the associated constant `BIJECTION`.
+#[derive(Clone, Copy, Debug)]
+pub struct Bijection<T> {
+    pub forward: fn(T) -> T,
+    pub inverse: fn(T) -> T,
+}
+
+impl<T> Bijection<T> {
+    /// Applies the formula.
+    pub fn forward(&self, value: T) -> T {
+        (self.forward)(value)
+    }
+
+    /// Applies the inverse of the formula.
+    pub fn inverse(&self, value: T) -> T {
+        (self.inverse)(value)
+    }
+}
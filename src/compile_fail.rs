@@ -0,0 +1,432 @@
+//! Formulas and options the derive must reject with a compile error.
+//!
+//! An undefined name in a formula using `let` bindings:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(let = "SCALE = 3", expr = "|| a * SCALE + OFFSET")]
+//! struct Test;
+//! ```
+//!
+//! An enum without a `variant` naming the value to invert:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("|| a + 2")]
+//! enum Test {
+//!     Raw(usize),
+//! }
+//! ```
+//!
+//! A float literal in a formula with an integer type:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a * 2.0", ty = "u32")]
+//! struct Test;
+//! ```
+//!
+//! A `result_wrap` with generic arguments, which cannot be called to wrap the result:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! struct Meters<T>(T);
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a * 3", result_wrap = "Meters<usize>")]
+//! struct Test;
+//! ```
+//!
+//! A `result_wrap` which is not a tuple struct with a single field, which rustc reports at the
+//! option as the derive cannot see the struct:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! struct Meters {
+//!     value: usize,
+//! }
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a * 3", result_wrap = "Meters")]
+//! struct Test;
+//! ```
+//!
+//! A hand-written inverse which does not match the formula:
+//!
+//! ```compile_fail
+//! proc_lineq_derive::assert_inverses!(|| a + 2, |x| x + 2);
+//! ```
+//!
+//! A `solve_for` which is not one of the closure's parameters:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|a, b| a * b + 2", solve_for = "c")]
+//! struct Test;
+//! ```
+//!
+//! A `cfg` which is not a valid predicate:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a + 1", cfg = "target_os = ")]
+//! struct Test;
+//! ```
+//!
+//! A `nonzero_input` formula whose inverse does not divide by the input:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a * 2", nonzero_input = true)]
+//! struct Test;
+//! ```
+//!
+//! An inverse which overflows for almost every input, with `overflow_check = "deny"`:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a + u8::MAX", ty = "u8", overflow_check = "deny")]
+//! struct Test;
+//! ```
+//!
+//! An inverse which overflows for almost every input, with `overflow_check = "warn"`, where
+//! warnings about deprecated items are denied as the warning is given by using one:
+//!
+//! ```compile_fail
+//! #![deny(deprecated)]
+//!
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a + u8::MAX", ty = "u8", overflow_check = "warn")]
+//! struct Test;
+//! ```
+//!
+//! `gen_doctest` without any samples to document:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a + 1", gen_doctest = true)]
+//! struct Test;
+//! ```
+//!
+//! A `post_fn` which is not a path:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a + 1", post_fn = "1 + 1")]
+//! struct Test;
+//! ```
+//!
+//! A `solve_for` which never appears in the formula:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("|| x + 2", solve_for = "y")]
+//! struct Test;
+//! ```
+//!
+//! A `solve_for` which is not an identifier:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("|| x + 2", solve_for = "2x")]
+//! struct Test;
+//! ```
+//!
+//! `extra_bounds` which are not where-clause predicates:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a + 2", extra_bounds = "core::fmt::Debug")]
+//! struct Test;
+//! ```
+//!
+//! Both `ty` and its alias `type`:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a + 2", ty = "i64", type = "i32")]
+//! struct Test;
+//! ```
+//!
+//! A closure with several parameters and no `solve_for` choosing between them:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("|a, b| a * b + 2")]
+//! struct Test;
+//! ```
+//!
+//! `exact` with a `variant`, whose `calculate` already returns an `Option`:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a * 2", variant = "Raw", exact = true)]
+//! enum Test {
+//!     Raw(usize),
+//! }
+//! ```
+//!
+//! A formula whose output is constant once the target cancels out:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("|| (a - a) + 5")]
+//! struct Test;
+//! ```
+//!
+//! `deref_input` with a `gen_test`, which passes inputs by value:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a + 2", deref_input = true, gen_test = "roundtrip")]
+//! struct Test;
+//! ```
+//!
+//! A negated target with an unsigned type:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("|| -a")]
+//! struct Test;
+//! ```
+//!
+//! The modulo operator, which cannot be inverted:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("|| a % 3")]
+//! struct Test;
+//! ```
+//!
+//! A formula which is not an expression:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("a +")]
+//! struct Test;
+//! ```
+//!
+//! An attribute without options:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert]
+//! struct Test;
+//! ```
+//!
+//! Two attributes generating the same method:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("|| a + 2")]
+//! #[invert("|| a * 3")]
+//! struct Test;
+//! ```
+//!
+//! A `method` which is not an identifier:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("|| a + 2", method = "fn")]
+//! struct Test;
+//! ```
+//!
+//! A type with attributes, but no `invert` attribute:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! /// Documented, but with nothing to invert.
+//! #[derive(ClosureInverter)]
+//! #[allow(dead_code)]
+//! struct Test;
+//! ```
+//!
+//! `verify` with a formula whose inverse truncates:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("|| a / 2", verify = true)]
+//! struct Test;
+//! ```
+//!
+//! An equation without its parameter alone on one side:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("|y| a + 2 == y * 3")]
+//! struct Test;
+//! ```
+//!
+//! `const_fn` with `trace_calls`, which formats its arguments:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("|| a + 2", const_fn = true, trace_calls = true)]
+//! struct Test;
+//! ```
+//!
+//! A formula dividing the target by a literal zero:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("|| a / 0 + 1")]
+//! struct Test;
+//! ```
+//!
+//! A suffixed literal of another type than the formula's:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a * 2u8", ty = "u32")]
+//! struct Test;
+//! ```
+//!
+//! A function for `#[invert]` taking more than the value to invert:
+//!
+//! ```compile_fail
+//! #[proc_lineq_derive::invert("|| a + 2")]
+//! fn decode(value: usize, scale: usize) -> usize;
+//! ```
+//!
+//! `saturate_domain` with a float domain excluding its upper bound, which has no value below it
+//! to clamp to:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a * 2.0", ty = "f64", domain = "0.0..10.0", saturate_domain = true)]
+//! struct Test;
+//! ```
+//!
+//! `exact` with a float type, whose division leaves no remainder:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a * 2.0", ty = "f64", exact = true)]
+//! struct Test;
+//! ```
+//!
+//! `exact` with a formula whose float literals make its type `f64`:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a * 2.5", exact = true)]
+//! struct Test;
+//! ```
+//!
+//! `steps` with an inverse which is not a chain of single operations, as a root:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a.pow(2) + 1", steps = true)]
+//! struct Test;
+//! ```
+//!
+//! `.pow()` in a formula whose type is `f64`, as its inverse is an integer root:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a.pow(2) + 1", ty = "f64")]
+//! struct Test;
+//! ```
+//!
+//! `.pow()` in a function filled in by `#[invert]` taking an `f32`:
+//!
+//! ```compile_fail
+//! #[proc_lineq_derive::invert("|| a.pow(2)")]
+//! fn decode(value: f32) -> f32;
+//! ```
+//!
+//! `verify` with a formula referring to `Self`, which the check outside the impl cannot name:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert("|| a * Self::SCALE + 1", verify = true)]
+//! struct Test;
+//!
+//! impl Test {
+//!     const SCALE: usize = 3;
+//! }
+//! ```
+//!
+//! A `factory` constant whose name in lower case is a keyword which cannot be a parameter:
+//!
+//! ```compile_fail
+//! use proc_lineq_derive::ClosureInverter;
+//!
+//! #[derive(ClosureInverter)]
+//! #[invert(expr = "|| a * SELF", factory = "make_inverse")]
+//! struct Test;
+//! ```
@@ -0,0 +1,45 @@
+use crate::{ClosureInverter, ParseError};
+use proc_macro2::Ident;
+use syn::visit_mut::{self, VisitMut};
+use syn::{Expr, ExprClosure};
+
+impl ClosureInverter {
+    /// Composes closures left to right into a single closure, so `|| a * 2` followed by
+    /// `|| a + 3` gives `|| a * 2 + 3`.
+    ///
+    /// Each closure's body replaces `solve_for` in the body of the next.
+    pub fn compose(&self, closures: &[ExprClosure]) -> Result<ExprClosure, ParseError> {
+        let (first, rest) = closures
+            .split_first()
+            .ok_or_else(|| ParseError::Syntax("no closures to compose".to_owned()))?;
+        let mut body = Self::body(first)?.clone();
+        for closure in rest {
+            let mut next = Self::body(closure)?.clone();
+            substitute(&mut next, &self.solve_for, &body);
+            body = next;
+        }
+        // Built directly rather than through tokens, which would add parentheses for grouping
+        let mut composed = first.clone();
+        composed.body = Box::new(body);
+        Ok(composed)
+    }
+}
+
+/// Replaces every use of `ident` in `e` with `replacement`.
+pub(crate) fn substitute(e: &mut Expr, ident: &Ident, replacement: &Expr) {
+    Substitute { ident, replacement }.visit_expr_mut(e);
+}
+
+struct Substitute<'a> {
+    ident: &'a Ident,
+    replacement: &'a Expr,
+}
+
+impl VisitMut for Substitute<'_> {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        match e {
+            Expr::Path(p) if p.path.is_ident(self.ident) => *e = self.replacement.clone(),
+            _ => visit_mut::visit_expr_mut(self, e),
+        }
+    }
+}
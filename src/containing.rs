@@ -0,0 +1,65 @@
+use crate::ClosureInverter;
+use proc_macro2::Ident;
+use std::collections::HashSet;
+use syn::Expr;
+
+/// The subexpressions of a formula which contain the target, found in a single pass.
+///
+/// Each step of the inversion looks its operands up here rather than searching them again,
+/// which would scan a deeply nested formula once for every level of nesting. Subexpressions are
+/// known by address, which stays fixed as operands are moved out of their boxes.
+pub(crate) struct Containing(HashSet<*const Expr>);
+
+impl Containing {
+    pub fn new(e: &Expr, target: &Ident) -> Self {
+        let mut containing = HashSet::new();
+        mark(e, target, &mut containing);
+        Self(containing)
+    }
+
+    /// Returns true if `e`, a boxed operand within the formula, contains the target.
+    pub fn contains(&self, e: &Expr) -> bool {
+        self.0.contains(&(e as *const Expr))
+    }
+}
+
+fn mark(e: &Expr, target: &Ident, containing: &mut HashSet<*const Expr>) -> bool {
+    let contains = match e {
+        // Both operands are marked, so neither is searched again later
+        Expr::Binary(b) => {
+            let left = mark(&b.left, target, containing);
+            let right = mark(&b.right, target, containing);
+            left || right
+        }
+        Expr::MethodCall(m) => mark(&m.receiver, target, containing),
+        Expr::Paren(p) => mark(&p.expr, target, containing),
+        Expr::Group(g) => mark(&g.expr, target, containing),
+        Expr::Cast(c) => mark(&c.expr, target, containing),
+        Expr::Unary(u) => mark(&u.expr, target, containing),
+        e => ClosureInverter::check_contains_target(e, target),
+    };
+    if contains {
+        containing.insert(e);
+    }
+    contains
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::format_ident;
+    use syn::parse_quote;
+
+    #[test]
+    fn mark_target_path() {
+        let a = format_ident!("a");
+        let e: Expr = parse_quote!((a + 1) * (2 + 3));
+        let containing = Containing::new(&e, &a);
+        let Expr::Binary(product) = &e else {
+            unreachable!()
+        };
+        assert!(containing.contains(&e));
+        assert!(containing.contains(&product.left));
+        assert!(!containing.contains(&product.right));
+    }
+}
@@ -0,0 +1,77 @@
+use crate::{fold, ClosureInverter, ParseError};
+use syn::{BinOp, Expr, ExprClosure};
+
+impl ClosureInverter {
+    /// Returns the degree of the closure body in `solve_for` once it is folded, counting a
+    /// division by the target as a negative power. Calls which are inverted as a whole, `abs`
+    /// and `pow`, count as their receiver.
+    ///
+    /// `|| a * 2 + 1` is degree 1, `|| a * a` degree 2 and `|| 100 / a` degree -1.
+    pub fn degree(&self, closure: &ExprClosure) -> Result<i32, ParseError> {
+        let body = fold::fold(Self::body(closure)?.clone());
+        Ok(self.expr_degree(&body))
+    }
+
+    /// Rejects a closure whose body is of a degree above 1 in `solve_for`, reporting the degree.
+    pub fn require_affine(&self, closure: &ExprClosure) -> Result<(), ParseError> {
+        match self.degree(closure)? {
+            degree if degree > 1 => Err(ParseError::NonAffine {
+                degree,
+                target: self.solve_for.to_string(),
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    fn expr_degree(&self, e: &Expr) -> i32 {
+        match e {
+            Expr::Binary(b) => {
+                let left = self.expr_degree(&b.left);
+                let right = self.expr_degree(&b.right);
+                match b.op {
+                    BinOp::Mul(_) => left + right,
+                    BinOp::Div(_) => left - right,
+                    _ => left.max(right),
+                }
+            }
+            Expr::Path(p) if Self::parse_path(p, &self.solve_for) => 1,
+            Expr::Paren(p) => self.expr_degree(&p.expr),
+            Expr::Group(g) => self.expr_degree(&g.expr),
+            Expr::Unary(u) => self.expr_degree(&u.expr),
+            Expr::Cast(c) => self.expr_degree(&c.expr),
+            Expr::MethodCall(m) => self.expr_degree(&m.receiver),
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::format_ident;
+    use syn::parse_quote;
+
+    #[test]
+    fn degree_of_formulas() {
+        let eq = ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert_eq!(eq.degree(&parse_quote!(|| 5 + 2)).unwrap(), 0);
+        assert_eq!(eq.degree(&parse_quote!(|| (a - a) + 5)).unwrap(), 0);
+        assert_eq!(eq.degree(&parse_quote!(|| a * 2 + 1)).unwrap(), 1);
+        assert_eq!(eq.degree(&parse_quote!(|| 100 / a)).unwrap(), -1);
+        assert_eq!(eq.degree(&parse_quote!(|| a * a)).unwrap(), 2);
+        assert_eq!(eq.degree(&parse_quote!(|| a * a + a)).unwrap(), 2);
+        assert_eq!(eq.degree(&parse_quote!(|| a * 0 * a + a)).unwrap(), 1);
+        assert_eq!(eq.degree(&parse_quote!(|| a.pow(2) + 1)).unwrap(), 1);
+    }
+
+    #[test]
+    fn require_affine_formulas() {
+        let eq = ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert!(eq.require_affine(&parse_quote!(|| 5 + 2)).is_ok());
+        assert!(eq.require_affine(&parse_quote!(|| a * 2 + 1)).is_ok());
+        let error = eq.require_affine(&parse_quote!(|| a * a + a)).unwrap_err();
+        assert_eq!(error.to_string(), "formula is degree 2 in `a`");
+        let error = eq.require_affine(&parse_quote!(|| a * a)).unwrap_err();
+        assert!(matches!(error, ParseError::NonAffine { degree: 2, .. }));
+    }
+}
@@ -0,0 +1,23 @@
+use core::fmt;
+
+/// Why a generated inverse has no result for a value.
+///
+/// Returned by `calculate` when generated with `#[invert(exact = true)]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InversionError<T> {
+    /// No input gives the value, as a step of the inverse divides with a remainder, such as
+    /// `5 / 2` when inverting `|| a * 2`.
+    NotExact { value: T, divisor: T },
+}
+
+impl<T: fmt::Display> fmt::Display for InversionError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotExact { value, divisor } => {
+                write!(f, "{} is not divisible by {}", value, divisor)
+            }
+        }
+    }
+}
+
+impl<T: fmt::Debug + fmt::Display> core::error::Error for InversionError<T> {}
@@ -0,0 +1,142 @@
+use proc_macro2::Ident;
+use syn::{BinOp, Expr, Lit, Path, Type, UnOp};
+
+/// The range of values a primitive integer type can hold.
+///
+/// Values are held as `i128`, so the upper half of `u128` is not representable.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct IntType {
+    pub min: i128,
+    pub max: i128,
+}
+
+impl IntType {
+    /// Returns the range of a primitive integer type, or `None` for any other type.
+    pub fn from_type(ty: &Type) -> Option<Self> {
+        match ty {
+            Type::Path(p) => Self::from_ident(p.path.get_ident()?),
+            _ => None,
+        }
+    }
+
+    fn from_ident(ident: &Ident) -> Option<Self> {
+        let (min, max) = match ident.to_string().as_str() {
+            "u8" => (0, u8::MAX as i128),
+            "u16" => (0, u16::MAX as i128),
+            "u32" => (0, u32::MAX as i128),
+            "u64" => (0, u64::MAX as i128),
+            "u128" => (0, i128::MAX),
+            "usize" => (0, usize::MAX as i128),
+            "i8" => (i8::MIN as i128, i8::MAX as i128),
+            "i16" => (i16::MIN as i128, i16::MAX as i128),
+            "i32" => (i32::MIN as i128, i32::MAX as i128),
+            "i64" => (i64::MIN as i128, i64::MAX as i128),
+            "i128" => (i128::MIN, i128::MAX),
+            "isize" => (isize::MIN as i128, isize::MAX as i128),
+            _ => return None,
+        };
+        Some(Self { min, max })
+    }
+
+    fn check(self, value: i128) -> Option<i128> {
+        (self.min..=self.max).contains(&value).then_some(value)
+    }
+}
+
+/// Returns the value of an associated bound such as `u8::MAX` or `i32::MIN`.
+fn bound(path: &Path) -> Option<i128> {
+    match path.segments.iter().collect::<Vec<_>>().as_slice() {
+        [ty, bound] if ty.arguments.is_none() && bound.arguments.is_none() => {
+            let ty = IntType::from_ident(&ty.ident)?;
+            match bound.ident.to_string().as_str() {
+                "MIN" => Some(ty.min),
+                "MAX" => Some(ty.max),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Returns true if [`eval`] understands every part of the expression, so a `None` from it can
+/// only come from overflow or division by zero.
+pub(crate) fn is_evaluable(e: &Expr, ident: &Ident) -> bool {
+    match e {
+        Expr::Binary(b) => {
+            matches!(
+                b.op,
+                BinOp::Add(_) | BinOp::Sub(_) | BinOp::Mul(_) | BinOp::Div(_)
+            ) && is_evaluable(&b.left, ident)
+                && is_evaluable(&b.right, ident)
+        }
+        Expr::Lit(l) => matches!(&l.lit, Lit::Int(i) if i.base10_parse::<i128>().is_ok()),
+        Expr::Paren(p) => is_evaluable(&p.expr, ident),
+        Expr::Group(g) => is_evaluable(&g.expr, ident),
+        Expr::Path(p) => p.path.is_ident(ident) || bound(&p.path).is_some(),
+        Expr::Unary(u) => matches!(u.op, UnOp::Neg(_)) && is_evaluable(&u.expr, ident),
+        _ => false,
+    }
+}
+
+/// Evaluates an expression as the integer type would, with `ident` bound to `value`.
+///
+/// Returns `None` if any step overflows the type, divides by zero, or uses something which
+/// cannot be evaluated such as an unknown constant.
+pub(crate) fn eval(e: &Expr, ident: &Ident, value: i128, ty: IntType) -> Option<i128> {
+    match e {
+        Expr::Binary(b) => {
+            let left = eval(&b.left, ident, value, ty)?;
+            let right = eval(&b.right, ident, value, ty)?;
+            let result = match b.op {
+                BinOp::Add(_) => left.checked_add(right)?,
+                BinOp::Sub(_) => left.checked_sub(right)?,
+                BinOp::Mul(_) => left.checked_mul(right)?,
+                BinOp::Div(_) => left.checked_div(right)?,
+                _ => return None,
+            };
+            ty.check(result)
+        }
+        Expr::Lit(l) => match &l.lit {
+            Lit::Int(i) => ty.check(i.base10_parse().ok()?),
+            _ => None,
+        },
+        Expr::Paren(p) => eval(&p.expr, ident, value, ty),
+        Expr::Group(g) => eval(&g.expr, ident, value, ty),
+        Expr::Path(p) if p.path.is_ident(ident) => ty.check(value),
+        Expr::Path(p) => ty.check(bound(&p.path)?),
+        Expr::Unary(u) if matches!(u.op, UnOp::Neg(_)) => {
+            ty.check(eval(&u.expr, ident, value, ty)?.checked_neg()?)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::format_ident;
+    use syn::parse_quote;
+
+    #[test]
+    fn eval_with_type_bounds() {
+        let a = format_ident!("a");
+        let u8_ty = IntType::from_type(&parse_quote!(u8)).unwrap();
+        let e: Expr = parse_quote!(a * 2 + 1);
+        assert_eq!(eval(&e, &a, 5, u8_ty), Some(11));
+        assert_eq!(eval(&e, &a, 200, u8_ty), None);
+
+        let e: Expr = parse_quote!(a - 5);
+        assert_eq!(eval(&e, &a, 3, u8_ty), None);
+        let i8_ty = IntType::from_type(&parse_quote!(i8)).unwrap();
+        assert_eq!(eval(&e, &a, 3, i8_ty), Some(-2));
+
+        let e: Expr = parse_quote!(100 / a);
+        assert_eq!(eval(&e, &a, 0, u8_ty), None);
+
+        let e: Expr = parse_quote!(a - u8::MAX);
+        assert_eq!(eval(&e, &a, 255, u8_ty), Some(0));
+        assert_eq!(eval(&e, &a, 254, u8_ty), None);
+        assert!(is_evaluable(&e, &a));
+        assert!(!is_evaluable(&parse_quote!(a - SCALE), &a));
+    }
+}
@@ -0,0 +1,355 @@
+use proc_macro2::Ident;
+use syn::{parse_quote, BinOp, Expr, ExprBinary, ExprLit, ExprParen, Lit, LitInt, UnOp};
+
+/// Simplifies an expression before inversion by removing terms which cannot change its value.
+///
+/// Anything multiplied by a literal zero is replaced by that zero, so `a + 0 * (a + 5)` is
+/// counted as a single occurrence of `a` rather than rejected as multiple. A multiplication
+/// followed by a division which divides it exactly is combined, so `a * 6 / 3` becomes `a * 2`.
+/// Integer constants along a chain of additions and subtractions are summed into one offset, so
+/// `1 + 2 + a - 4` becomes `a - 1`, and a term both added and subtracted cancels, so
+/// `(a - a) + 5` becomes `5`. A negated literal is summed with the opposite sign, so `a + -2`
+/// becomes `a - 2`.
+pub(crate) fn fold(e: Expr) -> Expr {
+    match e {
+        Expr::Binary(mut b) => {
+            *b.left = fold(*b.left);
+            *b.right = fold(*b.right);
+            match b.op {
+                BinOp::Mul(_) if is_zero(&b.left) => *b.left,
+                BinOp::Mul(_) if is_zero(&b.right) => *b.right,
+                BinOp::Add(_) if is_zero(&b.left) => *b.right,
+                BinOp::Add(_) | BinOp::Sub(_) if is_zero(&b.right) => *b.left,
+                BinOp::Div(_) => match fold_mul_div(&b.left, &b.right) {
+                    Some(e) => e,
+                    None => Expr::Binary(b),
+                },
+                BinOp::Add(_) | BinOp::Sub(_) => fold_additive(Expr::Binary(b)),
+                _ => Expr::Binary(b),
+            }
+        }
+        // An invisible group from a macro expansion groups like parentheses
+        Expr::Group(g) => fold(Expr::Paren(ExprParen {
+            attrs: g.attrs,
+            paren_token: Default::default(),
+            expr: g.expr,
+        })),
+        // Grouping is only dropped once nothing is left to group
+        Expr::Paren(mut p) => {
+            *p.expr = fold(*p.expr);
+            match *p.expr {
+                e @ (Expr::Lit(_) | Expr::Path(_)) => e,
+                _ => Expr::Paren(p),
+            }
+        }
+        e => e,
+    }
+}
+
+/// Returns true if any subexpression is multiplied by a literal zero.
+pub(crate) fn has_zero_product(e: &Expr) -> bool {
+    match e {
+        Expr::Binary(b) => {
+            (matches!(b.op, BinOp::Mul(_)) && (is_zero(&b.left) || is_zero(&b.right)))
+                || has_zero_product(&b.left)
+                || has_zero_product(&b.right)
+        }
+        Expr::Paren(p) => has_zero_product(&p.expr),
+        Expr::Group(g) => has_zero_product(&g.expr),
+        _ => false,
+    }
+}
+
+/// Returns a form of the expression suitable for comparison, without grouping parentheses and
+/// with `from` renamed to `to`.
+pub(crate) fn canonical(e: &Expr, from: &Ident, to: &Ident) -> Expr {
+    let e = match e {
+        Expr::Binary(b) => {
+            let mut b = b.clone();
+            *b.left = canonical(&b.left, from, to);
+            *b.right = canonical(&b.right, from, to);
+            Expr::Binary(b)
+        }
+        Expr::Paren(p) => canonical(&p.expr, from, to),
+        Expr::Group(g) => canonical(&g.expr, from, to),
+        Expr::Path(p) if p.path.is_ident(from) => parse_quote!(#to),
+        e => e.clone(),
+    };
+    fold(e)
+}
+
+/// Sums the integer literals in a chain of additions and subtractions into a single trailing
+/// offset, keeping the other terms in order. A term both added and subtracted cancels out.
+fn fold_additive(e: Expr) -> Expr {
+    let mut terms = vec![];
+    flatten_additive(&e, true, &mut terms);
+    // A negated literal is the literal itself taken with the opposite sign
+    let mut negated = false;
+    for (positive, term) in &mut terms {
+        if let Expr::Unary(u) = term {
+            if matches!(u.op, UnOp::Neg(_)) && int_value(&u.expr).is_some() {
+                *positive = !*positive;
+                *term = &u.expr;
+                negated = true;
+            }
+        }
+    }
+
+    let mut offset: i128 = 0;
+    let mut literals = vec![];
+    let mut others = vec![];
+    for (positive, term) in terms {
+        match int_value(term) {
+            Some(value) if literals.is_empty() || same_suffix(literals[0], term) => {
+                let value = value as i128;
+                offset += if positive { value } else { -value };
+                literals.push(term);
+            }
+            _ => others.push((positive, term)),
+        }
+    }
+
+    let mut cancelled = false;
+    let mut i = 0;
+    while i < others.len() {
+        let (positive, term) = others[i];
+        match (i + 1..others.len()).find(|&j| others[j].0 != positive && others[j].1 == term) {
+            Some(j) => {
+                others.remove(j);
+                others.remove(i);
+                cancelled = true;
+            }
+            None => i += 1,
+        }
+    }
+    // Constants alone are summed unless that would need a negative literal
+    if others.is_empty() && (cancelled || (literals.len() > 1 && offset >= 0)) {
+        let literal = match literals.first() {
+            Some(like) => int_lit(offset.unsigned_abs(), Some(like)),
+            None => parse_quote!(0),
+        };
+        return if offset < 0 {
+            parse_quote!(-#literal)
+        } else {
+            literal
+        };
+    }
+
+    // Nothing to combine, or no positive term to start the chain from
+    let lead = others.iter().position(|(positive, _)| *positive);
+    let lead = match lead {
+        Some(lead) if literals.len() > 1 || cancelled || negated => lead,
+        _ => return e,
+    };
+
+    let mut folded = others[lead].1.clone();
+    for (i, (positive, term)) in others.iter().enumerate() {
+        if i != lead {
+            folded = binary(folded, additive_op(*positive), (*term).clone());
+        }
+    }
+    if offset != 0 {
+        let literal = int_lit(offset.unsigned_abs(), Some(literals[0]));
+        folded = binary(folded, additive_op(offset > 0), literal);
+    }
+    folded
+}
+
+/// Collects the terms of an additive chain with whether each is added or subtracted.
+fn flatten_additive<'a>(e: &'a Expr, positive: bool, terms: &mut Vec<(bool, &'a Expr)>) {
+    match e {
+        Expr::Binary(b) if matches!(b.op, BinOp::Add(_)) => {
+            flatten_additive(&b.left, positive, terms);
+            flatten_additive(&b.right, positive, terms);
+        }
+        Expr::Binary(b) if matches!(b.op, BinOp::Sub(_)) => {
+            flatten_additive(&b.left, positive, terms);
+            flatten_additive(&b.right, !positive, terms);
+        }
+        _ => terms.push((positive, e)),
+    }
+}
+
+fn same_suffix(a: &Expr, b: &Expr) -> bool {
+    match (a, b) {
+        (
+            Expr::Lit(ExprLit {
+                lit: Lit::Int(a), ..
+            }),
+            Expr::Lit(ExprLit {
+                lit: Lit::Int(b), ..
+            }),
+        ) => a.suffix() == b.suffix(),
+        _ => false,
+    }
+}
+
+fn additive_op(positive: bool) -> BinOp {
+    if positive {
+        BinOp::Add(Default::default())
+    } else {
+        BinOp::Sub(Default::default())
+    }
+}
+
+fn binary(left: Expr, op: BinOp, right: Expr) -> Expr {
+    Expr::Binary(ExprBinary {
+        attrs: vec![],
+        left: Box::new(left),
+        op,
+        right: Box::new(right),
+    })
+}
+
+/// Folds `x * n / d` into `x * (n / d)` when `d` divides `n`.
+fn fold_mul_div(left: &Expr, divisor: &Expr) -> Option<Expr> {
+    let d = int_value(divisor)?;
+    match left {
+        Expr::Binary(m) if matches!(m.op, BinOp::Mul(_)) => {
+            let mut folded = m.clone();
+            let lit = match (int_value(&m.left), int_value(&m.right)) {
+                (_, Some(n)) if d != 0 && n % d == 0 => &mut folded.right,
+                (Some(n), None) if d != 0 && n % d == 0 => &mut folded.left,
+                _ => return None,
+            };
+            **lit = int_lit(int_value(lit)? / d, Some(lit));
+            Some(Expr::Binary(folded))
+        }
+        _ => None,
+    }
+}
+
+/// Builds an integer literal with the value given, keeping the suffix and span of the integer
+/// literal `like`, or unsuffixed without one.
+pub(crate) fn int_lit(value: u128, like: Option<&Expr>) -> Expr {
+    let lit = match like.and_then(lit_int) {
+        Some(i) => LitInt::new(&format!("{}{}", value, i.suffix()), i.span()),
+        None => LitInt::from(proc_macro2::Literal::u128_unsuffixed(value)),
+    };
+    Expr::Lit(ExprLit {
+        attrs: vec![],
+        lit: Lit::Int(lit),
+    })
+}
+
+/// Returns the value of an integer literal, which may be in parentheses.
+pub(crate) fn int_value(e: &Expr) -> Option<u128> {
+    lit_int(e)?.base10_parse().ok()
+}
+
+fn lit_int(e: &Expr) -> Option<&LitInt> {
+    match e {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(i), ..
+        }) => Some(i),
+        Expr::Paren(p) => lit_int(&p.expr),
+        Expr::Group(g) => lit_int(&g.expr),
+        _ => None,
+    }
+}
+
+/// Returns true if the expression is the integer literal zero.
+pub(crate) fn is_zero(e: &Expr) -> bool {
+    match e {
+        Expr::Lit(l) => match &l.lit {
+            Lit::Int(i) => matches!(i.base10_parse::<u128>(), Ok(0)),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn build_int_literals() {
+        assert_eq!(int_value(&parse_quote!(12u8)), Some(12));
+        assert_eq!(int_value(&parse_quote!((12))), Some(12));
+        assert_eq!(int_value(&parse_quote!(a)), None);
+
+        let like: Expr = parse_quote!((3u16));
+        assert_eq!(int_lit(7, Some(&like)), parse_quote!(7u16));
+        assert_eq!(int_lit(7, None), parse_quote!(7));
+    }
+
+    #[test]
+    fn fold_zero_multiplied_target() {
+        let e: Expr = parse_quote!(a + 0 * a);
+        assert_eq!(fold(e), parse_quote!(a));
+
+        let e: Expr = parse_quote!(a * 0 + 3);
+        assert_eq!(fold(e), parse_quote!(3));
+
+        let e: Expr = parse_quote!(a + 0 * (a + 5));
+        assert_eq!(fold(e), parse_quote!(a));
+    }
+
+    #[test]
+    fn fold_exact_multiply_divide() {
+        let e: Expr = parse_quote!(a * 6 / 3);
+        assert_eq!(fold(e), parse_quote!(a * 2));
+
+        let e: Expr = parse_quote!(6 * a / 3 + 1);
+        assert_eq!(fold(e), parse_quote!(2 * a + 1));
+
+        let e: Expr = parse_quote!(a * 7 / 2);
+        assert_eq!(fold(e), parse_quote!(a * 7 / 2));
+    }
+
+    #[test]
+    fn fold_additive_constants() {
+        let e: Expr = parse_quote!(1 + 2 + 3 + a - 4 - 5);
+        assert_eq!(fold(e), parse_quote!(a - 3));
+
+        let e: Expr = parse_quote!(10 + a * 2 - 3 + 1);
+        assert_eq!(fold(e), parse_quote!(a * 2 + 8));
+
+        // A single constant is left as written
+        let e: Expr = parse_quote!(10 - a);
+        assert_eq!(fold(e), parse_quote!(10 - a));
+
+        // Constants which cancel leave only the target
+        let e: Expr = parse_quote!(a + 4 - 4);
+        assert_eq!(fold(e), parse_quote!(a));
+    }
+
+    #[test]
+    fn fold_negative_literals() {
+        let e: Expr = parse_quote!(a + -2);
+        assert_eq!(fold(e), parse_quote!(a - 2));
+
+        let e: Expr = parse_quote!(-2 + a);
+        assert_eq!(fold(e), parse_quote!(a - 2));
+
+        let e: Expr = parse_quote!(a - -2 + 3);
+        assert_eq!(fold(e), parse_quote!(a + 5));
+
+        // Nothing positive to lead the chain
+        let e: Expr = parse_quote!(-2 - a);
+        assert_eq!(fold(e), parse_quote!(-2 - a));
+    }
+
+    #[test]
+    fn fold_grouped_constants() {
+        let e: Expr = parse_quote!(a - (10 + 20 + 30));
+        assert_eq!(fold(e), parse_quote!(a - 60));
+
+        // A negative sum has no literal to fold into
+        let e: Expr = parse_quote!(a - (2 - 3));
+        assert_eq!(fold(e), parse_quote!(a - (2 - 3)));
+    }
+
+    #[test]
+    fn fold_cancelled_terms() {
+        let e: Expr = parse_quote!((a - a) + 5);
+        assert_eq!(fold(e), parse_quote!(5));
+
+        let e: Expr = parse_quote!(a * 2 + b - a * 2 - 7);
+        assert_eq!(fold(e), parse_quote!(b - 7));
+
+        let e: Expr = parse_quote!(b + a - a);
+        assert_eq!(fold(e), parse_quote!(b));
+    }
+}
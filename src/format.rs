@@ -0,0 +1,103 @@
+use quote::ToTokens;
+use syn::{Expr, ExprClosure};
+
+/// How the operators of a formula are spaced when it is written out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    /// A space either side of each binary operator, as in `|value| (value - 1) / 2`.
+    Pretty,
+    /// No spaces around operators, as in `|value|(value-1)/2`.
+    Compact,
+}
+
+/// Writes a closure out as source code in the given style.
+///
+/// ```
+/// use proc_lineq::{format_closure, ClosureInverter, Style};
+///
+/// let inverse = ClosureInverter::from_formula("a", "|| a * 2 + 1").unwrap();
+/// assert_eq!(format_closure(&inverse, Style::Pretty), "|value| (value - 1) / 2");
+/// assert_eq!(format_closure(&inverse, Style::Compact), "|value|(value-1)/2");
+/// ```
+pub fn format_closure(closure: &ExprClosure, style: Style) -> String {
+    let params: Vec<String> = closure.inputs.iter().map(compact_tokens).collect();
+    let mut out = format!("|{}|", params.join(", "));
+    if style == Style::Pretty {
+        out.push(' ');
+    }
+    write_expr(&closure.body, style, &mut out);
+    out
+}
+
+/// Writes an expression out as source code in the given style.
+pub(crate) fn format_expr(e: &Expr, style: Style) -> String {
+    let mut out = String::new();
+    write_expr(e, style, &mut out);
+    out
+}
+
+fn write_expr(e: &Expr, style: Style, out: &mut String) {
+    match e {
+        Expr::Binary(b) => {
+            write_expr(&b.left, style, out);
+            let op = compact_tokens(&b.op);
+            match style {
+                Style::Pretty => out.push_str(&format!(" {} ", op)),
+                Style::Compact => out.push_str(&op),
+            }
+            write_expr(&b.right, style, out);
+        }
+        Expr::Paren(p) => {
+            out.push('(');
+            write_expr(&p.expr, style, out);
+            out.push(')');
+        }
+        Expr::Unary(u) => {
+            out.push_str(&compact_tokens(&u.op));
+            write_expr(&u.expr, style, out);
+        }
+        // `as` is a keyword, so needs spaces in either style
+        Expr::Cast(c) => {
+            write_expr(&c.expr, style, out);
+            out.push_str(" as ");
+            out.push_str(&compact_tokens(&c.ty));
+        }
+        e => out.push_str(&compact_tokens(e)),
+    }
+}
+
+/// Writes tokens out without the spaces `quote` puts between them, except those separating two
+/// words such as `let value`.
+fn compact_tokens(tokens: &impl ToTokens) -> String {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let mut out = String::new();
+    for word in tokens.to_token_stream().to_string().split_whitespace() {
+        if out.ends_with(is_word) && word.starts_with(is_word) {
+            out.push(' ');
+        }
+        out.push_str(word);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn format_multi_op_inverse() {
+        let inverse: ExprClosure = parse_quote!(|value| 100 / ((value - 7) * 3) + -u8::MAX);
+        assert_eq!(
+            format_closure(&inverse, Style::Pretty),
+            "|value| 100 / ((value - 7) * 3) + -u8::MAX"
+        );
+        assert_eq!(
+            format_closure(&inverse, Style::Compact),
+            "|value|100/((value-7)*3)+-u8::MAX"
+        );
+
+        let inverse: ExprClosure = parse_quote!(|b| (b - 2) as _);
+        assert_eq!(format_closure(&inverse, Style::Compact), "|b|(b-2) as _");
+    }
+}
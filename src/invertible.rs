@@ -0,0 +1,21 @@
+/// A type with an inverse, so that generic code can apply any one of them.
+///
+/// Implemented by `#[derive(ClosureInverter)]` when `calculate` takes and returns the formula's
+/// own type, without a `variant`, extra parameters or wrapping of its input or output.
+/// The derive also generates an inherent `calculate` forwarding to the trait, as visible as the
+/// type, so calling it does not require importing the trait.
+///
+/// ```
+/// use proc_lineq::Invertible;
+///
+/// fn apply<T: Invertible<Num = usize>>(value: usize) -> usize {
+///     T::calculate(value)
+/// }
+/// ```
+pub trait Invertible {
+    /// The type the formula acts on.
+    type Num;
+
+    /// Applies the inverse of the formula.
+    fn calculate(value: Self::Num) -> Self::Num;
+}
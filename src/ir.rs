@@ -0,0 +1,19 @@
+/// One operation of an inverse, applied to the value produced by the operation before it.
+///
+/// Generated by `#[invert(emit_ir = true)]` as the associated constant `OPS`, in the order the
+/// operations are evaluated, so an interpreter can run the inverse from data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op<T> {
+    /// `x + n`
+    AddConst(T),
+    /// `x - n`
+    SubConst(T),
+    /// `n - x`
+    SubFrom(T),
+    /// `x * n`
+    MulConst(T),
+    /// `x / n`
+    DivConst(T),
+    /// `n / x`
+    ReciprocalInto(T),
+}
@@ -1,33 +1,198 @@
 #![warn(clippy::panic, clippy::str_to_string, clippy::panicking_unwrap)]
+// Without the solver, only the items generated code refers to are built
+#![cfg_attr(not(feature = "solver"), no_std)]
 
-use proc_macro2::{Ident, Span};
+// Lets code generated for tests name this crate as it would in a dependent
+#[cfg(test)]
+extern crate self as proc_lineq;
+
+mod bijection;
+#[cfg(doctest)]
+mod compile_fail;
+#[cfg(feature = "solver")]
+mod compose;
+#[cfg(feature = "solver")]
+mod containing;
+#[cfg(feature = "solver")]
+mod degree;
+mod error;
+#[cfg(feature = "solver")]
+mod eval;
+#[cfg(feature = "solver")]
+mod fold;
+#[cfg(feature = "solver")]
+mod format;
+mod invertible;
+mod ir;
+#[cfg(feature = "solver")]
+mod linear;
+#[cfg(feature = "solver")]
+mod rpn;
+#[cfg(feature = "solver")]
+mod sexpr;
+
+pub use bijection::Bijection;
+#[cfg(feature = "solver")]
+use containing::Containing;
+pub use error::InversionError;
+#[cfg(feature = "solver")]
+pub use format::{format_closure, Style};
+pub use invertible::Invertible;
+pub use ir::Op;
+#[cfg(feature = "solver")]
+use proc_macro2::{Ident, Span, TokenStream, TokenTree};
+#[cfg(feature = "solver")]
+use quote::ToTokens;
+#[cfg(feature = "solver")]
+use std::ops::RangeInclusive;
+#[cfg(feature = "solver")]
 use syn::spanned::Spanned;
-use syn::{parse_quote, BinOp, Expr, ExprBinary, ExprClosure, ExprPath, Token};
+#[cfg(feature = "solver")]
+use syn::{
+    parse_quote, BinOp, Expr, ExprBinary, ExprCast, ExprClosure, ExprLit, ExprMacro,
+    ExprMethodCall, ExprParen, ExprPath, ExprUnary, Lit, Pat, Stmt, Token, Type, TypeInfer, UnOp,
+};
+#[cfg(feature = "solver")]
 use thiserror::Error;
 
+/// Why a formula could not be inverted.
+///
+/// Errors found in a particular part of the formula carry the span of that part, see
+/// [`span`](Self::span).
+#[cfg(feature = "solver")]
 #[derive(Debug, Error)]
 pub enum ParseError {
+    #[error("the closure has {0} parameters, so which one to solve for is ambiguous")]
+    AmbiguousTarget(usize),
     #[error("only a subset of binary operators are allowed")]
-    BinOp,
+    BinOp(Span),
+    #[error("the target is multiplied or divided by a literal zero")]
+    DivideByZero(Span),
+    #[error("the inverse still refers to the variable being solved for")]
+    InternalInvariant,
     #[error("cannot have multiple of the target variable")]
-    Multiple,
+    Multiple(Span),
+    #[error("formula is degree {degree} in `{target}`")]
+    NonAffine { degree: i32, target: String },
+    #[error("{0} cannot be inverted")]
+    NonInvertibleOp(String, Span),
     #[error("solve_for not found")]
     NoSolveFor,
+    #[error("the formula uses constants which cannot be evaluated")]
+    NotEvaluable,
+    #[error("could not parse formula: {0}")]
+    Syntax(String),
+    #[error("the result does not depend on the target, as {0}")]
+    TargetEliminated(String),
     #[error("unexpected identifier")]
-    UnexpectedIdentifier,
+    UnexpectedIdentifier(Span),
     #[error("used unrecognised features")]
-    Validation,
+    Validation(Span),
+}
+
+#[cfg(feature = "solver")]
+impl ParseError {
+    /// The part of the formula the error was found in, if it was found in one part.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::BinOp(span)
+            | Self::DivideByZero(span)
+            | Self::Multiple(span)
+            | Self::NonInvertibleOp(_, span)
+            | Self::UnexpectedIdentifier(span)
+            | Self::Validation(span) => Some(*span),
+            _ => None,
+        }
+    }
+
+    /// Renders the error as a diagnostic for `source`, the formula it came from, in the style of
+    /// rustc.
+    ///
+    /// The part of the formula the error was found in is marked when its span locates it within
+    /// `source`, as it does for a formula parsed from that string. Otherwise the whole formula is.
+    pub fn render(&self, source: &str) -> String {
+        let width = source.lines().count().max(1).to_string().len();
+        let mut rendered = format!("error: {}\n{:width$}--> formula\n", self, "");
+        rendered.push_str(&format!("{:width$} |\n", ""));
+        // The line and columns of the marked part, which spans count in characters
+        let marked = self.span().and_then(|span| {
+            let (start, end) = (span.start(), span.end());
+            let line = source.lines().nth(start.line.checked_sub(1)?)?;
+            let columns = line.chars().count();
+            let end = if end.line == start.line {
+                end.column
+            } else {
+                columns
+            };
+            (start.column < end && end <= columns).then_some((start.line, start.column, end))
+        });
+        for (number, line) in source.lines().enumerate() {
+            rendered.push_str(&format!("{:width$} | {}\n", number + 1, line));
+            let (start, marked) = match marked {
+                Some((line, start, end)) if line == number + 1 => (start, end - start),
+                Some(_) => continue,
+                None => {
+                    let start = line.chars().count() - line.trim_start().chars().count();
+                    (start, line.trim().chars().count())
+                }
+            };
+            rendered.push_str(&format!(
+                "{:width$} | {}{}\n",
+                "",
+                " ".repeat(start),
+                "^".repeat(marked)
+            ));
+        }
+        rendered
+    }
 }
 
 /// Stores the variables and the current state of the calculation
 ///
 /// Call [`solve`] to build an output expression.
+#[cfg(feature = "solver")]
 pub struct ClosureInverter {
     target_expr: Box<Expr>,
     solve_for: Ident,
     target_ident: Ident,
+    non_negative: bool,
+    disjoint: bool,
+}
+
+/// Told of each operation undone while solving, when steps are being described.
+#[cfg(feature = "solver")]
+type Observer<'a> = Option<&'a mut dyn FnMut(&str, &Expr)>;
+
+/// What is left of an expression once its outermost operation is undone.
+#[cfg(feature = "solver")]
+#[derive(Debug)]
+enum Peeled {
+    /// The operand containing the target, with a step undoing the operation added to the inverse.
+    Step(Expr),
+    /// The operand containing the target, of grouping or an operation with no effect on it.
+    Unchanged(Expr),
+    /// Nothing, as the expression was the target itself.
+    Target,
+}
+
+/// Shows the identifiers and the inverse built so far as source code.
+#[cfg(feature = "solver")]
+impl std::fmt::Debug for ClosureInverter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClosureInverter")
+            .field("solve_for", &format_args!("{}", self.solve_for))
+            .field("target_ident", &format_args!("{}", self.target_ident))
+            .field(
+                "target_expr",
+                &format_args!("{}", self.target_expr.to_token_stream()),
+            )
+            .field("non_negative", &self.non_negative)
+            .field("disjoint", &self.disjoint)
+            .finish()
+    }
 }
 
+#[cfg(feature = "solver")]
 impl ClosureInverter {
     pub fn new(solve_for: Ident, target_ident: Ident) -> Self {
         Self {
@@ -38,83 +203,605 @@ impl ClosureInverter {
             })),
             solve_for,
             target_ident,
+            non_negative: false,
+            disjoint: false,
         }
     }
 
-    /// Returns true if is a valid expression to invert.
-    fn validate_expr(e: &Expr) -> bool {
+    /// The variable of the formula being solved for.
+    pub fn solve_for(&self) -> &Ident {
+        &self.solve_for
+    }
+
+    /// The parameter of the inverse, standing for the output of the formula.
+    pub fn target_ident(&self) -> &Ident {
+        &self.target_ident
+    }
+
+    /// Declares that only non-negative values of `solve_for` are of interest.
+    ///
+    /// This allows `abs` to be inverted, with the inverse giving the non-negative solution.
+    pub fn non_negative(mut self) -> Self {
+        self.non_negative = true;
+        self
+    }
+
+    /// Declares that the target never shares set bits with the constants it is combined with
+    /// using `|`.
+    ///
+    /// This allows `|| a | K` to be inverted as `|x| x & !K`.
+    pub fn disjoint(mut self) -> Self {
+        self.disjoint = true;
+        self
+    }
+
+    /// Checks the expression is one which can be inverted, returning an error spanning the
+    /// first part which is not.
+    fn validate_expr(e: &Expr) -> Result<(), ParseError> {
         match e {
-            Expr::Binary(b) => Self::validate_expr(&b.left) && Self::validate_expr(&b.right),
-            Expr::Lit(_) | Expr::Path(_) => true,
-            _ => false,
+            Expr::Binary(b) => {
+                Self::validate_expr(&b.left)?;
+                Self::validate_expr(&b.right)
+            }
+            Expr::Lit(_) | Expr::Path(_) => Ok(()),
+            Expr::MethodCall(m) if Self::is_abs(m) || Self::pow_exponent(m).is_some() => {
+                Self::validate_expr(&m.receiver)
+            }
+            Expr::Macro(m) if is_const_expr(m) => Ok(()),
+            Expr::Paren(_) | Expr::Group(_) => Self::validate_expr(strip_parens(e)),
+            Expr::Cast(c) => Self::validate_expr(&c.expr),
+            Expr::Unary(u) if matches!(u.op, UnOp::Neg(_)) => Self::validate_expr(&u.expr),
+            _ => Err(ParseError::Validation(e.span())),
+        }
+    }
+
+    fn is_abs(m: &ExprMethodCall) -> bool {
+        m.method == "abs" && m.args.is_empty() && m.turbofish.is_none()
+    }
+
+    /// Returns the exponent of `.pow(n)` with a positive integer literal `n`.
+    fn pow_exponent(m: &ExprMethodCall) -> Option<u32> {
+        if m.method != "pow" || m.turbofish.is_some() || m.args.len() != 1 {
+            return None;
+        }
+        match &m.args[0] {
+            Expr::Lit(ExprLit {
+                lit: Lit::Int(n), ..
+            }) => n.base10_parse::<u32>().ok().filter(|&n| n > 0),
+            _ => None,
+        }
+    }
+
+    /// Returns the expression a closure's body evaluates to.
+    ///
+    /// A block containing a single expression, or a single `return` with a value, is treated as
+    /// that value.
+    pub fn body(closure: &ExprClosure) -> Result<&Expr, ParseError> {
+        match &*closure.body {
+            Expr::Block(b) => match b.block.stmts.as_slice() {
+                [Stmt::Expr(Expr::Return(r), _)] => match &r.expr {
+                    Some(e) => Ok(e),
+                    None => Err(ParseError::Validation(r.span())),
+                },
+                [Stmt::Expr(e, None)] => Ok(e),
+                _ => Err(ParseError::Syntax(
+                    "a block must hold a single expression".to_owned(),
+                )),
+            },
+            e => Ok(e),
+        }
+    }
+
+    /// Returns the variable bound by a closure's single parameter, as `x` in `|x| x + 2`, or
+    /// `None` for a closure without parameters.
+    pub fn bound_param(closure: &ExprClosure) -> Result<Option<Ident>, ParseError> {
+        match closure.inputs.iter().collect::<Vec<_>>().as_slice() {
+            [] => Ok(None),
+            [Pat::Ident(p)] => Ok(Some(p.ident.clone())),
+            [Pat::Type(t)] => match &*t.pat {
+                Pat::Ident(p) => Ok(Some(p.ident.clone())),
+                pat => Err(ParseError::Validation(pat.span())),
+            },
+            [input] => Err(ParseError::Validation(input.span())),
+            inputs => Err(ParseError::AmbiguousTarget(inputs.len())),
         }
     }
 
     /// Parses a closure returning the inverse if possible.
-    pub fn solve(mut self, closure: &ExprClosure) -> Result<ExprClosure, ParseError> {
-        if Self::validate_expr(&closure.body) {
-            self.parse_expr(*closure.body.clone())?;
-
-            let target_expr = self.target_expr;
-            let target_ident = self.target_ident;
-            let c: ExprClosure = parse_quote!( |#target_ident| #target_expr);
-            Ok(c)
+    ///
+    /// `.pow(n)`, with a positive integer literal `n`, is inverted as the integer `n`th root. Only
+    /// exact roots recover the input, and others are rounded down. The root is found with
+    /// `checked_pow`, so the formula must act on a primitive integer type.
+    pub fn solve(self, closure: &ExprClosure) -> Result<ExprClosure, ParseError> {
+        self.solve_closure(closure, None)
+    }
+
+    /// Parses a closure like [`solve`], returning the inverse written out as tokens, such as
+    /// `| b | (b - 2) / 3`.
+    ///
+    /// Tokens are separated by single spaces, so the string is stable enough to compare against.
+    /// See [`format_closure`] for conventional spacing.
+    ///
+    /// [`solve`]: Self::solve
+    pub fn solve_to_string(self, closure: &ExprClosure) -> Result<String, ParseError> {
+        let inverse = self.solve(closure)?;
+        Ok(quote::quote!(#inverse).to_string())
+    }
+
+    /// Parses a closure like [`solve`], calling `observer` as each operation is undone with a
+    /// description of the operation, the operand being solved written as `_`, and the inverse
+    /// built so far.
+    ///
+    /// [`solve`]: Self::solve
+    pub fn solve_observed(
+        self,
+        closure: &ExprClosure,
+        observer: &mut dyn FnMut(&str, &Expr),
+    ) -> Result<ExprClosure, ParseError> {
+        self.solve_closure(closure, Some(observer))
+    }
+
+    /// Solves a closure, describing each step to the observer if there is one.
+    fn solve_closure(
+        self,
+        closure: &ExprClosure,
+        observer: Observer<'_>,
+    ) -> Result<ExprClosure, ParseError> {
+        let body = Self::body(closure)?;
+        // The inverse of an equation takes the same parameters, as `|y| y - 2` for `|y| a + 2 == y`
+        if let Expr::Binary(b) = strip_parens(body) {
+            if is_comparison(&b.op) {
+                let inputs = &closure.inputs;
+                let inverse = self.invert_equation(b, observer)?;
+                return Ok(parse_quote!(|#inputs| #inverse));
+            }
+        }
+        let target_ident = self.target_ident.clone();
+        let inverse = self.invert_body(body, observer)?;
+        Ok(parse_quote!(|#target_ident| #inverse))
+    }
+
+    /// Solves an equation such as `a + 2 == y` for `solve_for`, inverting the side containing it
+    /// onto the other.
+    fn invert_equation(
+        mut self,
+        equation: &ExprBinary,
+        observer: Observer<'_>,
+    ) -> Result<Expr, ParseError> {
+        if !matches!(equation.op, BinOp::Eq(_)) {
+            let op = &equation.op;
+            return Err(ParseError::NonInvertibleOp(
+                format!("the comparison `{}`", quote::quote!(#op)),
+                op.span(),
+            ));
+        }
+        let (left, right) = (&*equation.left, &*equation.right);
+        Self::validate_expr(left)?;
+        Self::validate_expr(right)?;
+        let (side, other) = match (
+            Self::check_contains_target(left, &self.solve_for),
+            Self::check_contains_target(right, &self.solve_for),
+        ) {
+            (true, false) => (left, right),
+            (false, true) => (right, left),
+            (true, true) => return Err(ParseError::Multiple(equation.span())),
+            (false, false) => return Err(ParseError::NoSolveFor),
+        };
+        self.target_expr = Box::new(strip_parens(other).clone());
+        self.invert_body(side, observer)
+    }
+
+    /// Inverts a formula given as an expression rather than a closure, returning the inverse as
+    /// an expression of `param`, for a caller building its own function around it.
+    ///
+    /// ```
+    /// use proc_lineq::ClosureInverter;
+    /// use quote::format_ident;
+    ///
+    /// let eq = ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+    /// let inverse = eq.solve_expr(&syn::parse_quote!(a * 2 + 1), format_ident!("y")).unwrap();
+    /// assert_eq!(inverse, syn::parse_quote!((y - 1) / 2));
+    /// ```
+    pub fn solve_expr(self, body: &Expr, param: Ident) -> Result<Expr, ParseError> {
+        let eq = Self {
+            target_expr: parse_quote!(#param),
+            target_ident: param,
+            ..self
+        };
+        eq.invert_body(body, None)
+    }
+
+    /// Inverts the body of a formula, returning the inverse as an expression of `target_ident`.
+    fn invert_body(mut self, body: &Expr, observer: Observer<'_>) -> Result<Expr, ParseError> {
+        let folded = fold::fold(body.clone());
+        Self::validate_expr(&folded)?;
+        let target = &self.solve_for;
+        if !tokens_contain(folded.to_token_stream(), target)
+            && tokens_contain(body.to_token_stream(), target)
+        {
+            let reason = if fold::has_zero_product(body) {
+                "every occurrence of it is multiplied by zero".to_owned()
+            } else {
+                format!(
+                    "it cancels out, leaving the constant output `{}`",
+                    folded.to_token_stream()
+                )
+            };
+            return Err(ParseError::TargetEliminated(reason));
+        }
+        let folded = if tokens_count(folded.to_token_stream(), target) > 1 {
+            self.require_affine(&parse_quote!(|| #folded))?;
+            match linear::collect_like_terms(&folded, target) {
+                Some(collected) => fold::fold(collected),
+                None => folded,
+            }
         } else {
-            Err(ParseError::Validation)
+            folded
+        };
+        self.parse_expr(folded, observer)?;
+        self.check_solved()?;
+        Ok(*self.target_expr)
+    }
+
+    /// Checks that no reference to `solve_for` was left behind in the inverse, which would
+    /// otherwise only surface as an unresolved name in the generated code.
+    fn check_solved(&self) -> Result<(), ParseError> {
+        if self.solve_for != self.target_ident
+            && tokens_contain(self.target_expr.to_token_stream(), &self.solve_for)
+        {
+            return Err(ParseError::InternalInvariant);
+        }
+        Ok(())
+    }
+
+    /// Returns true if the closure is its own inverse, such as `|| 100 - a`.
+    pub fn is_involution(&self, closure: &ExprClosure) -> Result<bool, ParseError> {
+        let forward = Self::body(closure)?;
+        let inverse =
+            Self::new(self.solve_for.clone(), self.target_ident.clone()).solve(closure)?;
+        Ok(
+            fold::canonical(&inverse.body, &self.target_ident, &self.solve_for)
+                == fold::canonical(forward, &self.solve_for, &self.solve_for),
+        )
+    }
+
+    /// Returns true if `inverse`, a closure taking a single parameter, is the inverse of
+    /// `closure` once both are put into a canonical form.
+    pub fn is_inverse(
+        &self,
+        closure: &ExprClosure,
+        inverse: &ExprClosure,
+    ) -> Result<bool, ParseError> {
+        let param = match inverse.inputs.iter().collect::<Vec<_>>().as_slice() {
+            [Pat::Ident(p)] => p.ident.clone(),
+            _ => return Err(ParseError::Validation(inverse.inputs.span())),
+        };
+        let solved = Self::new(self.solve_for.clone(), param.clone()).solve(closure)?;
+        let given = Self::body(inverse)?;
+        Ok(fold::canonical(&solved.body, &param, &param) == fold::canonical(given, &param, &param))
+    }
+
+    /// Returns the values of `solve_for` within `range` which the inverse recovers exactly when
+    /// evaluated as the integer type `ty`.
+    ///
+    /// For `|| a / 2` only even values are exact, as the division truncates. Values where either
+    /// direction overflows the type are excluded. Fails with [`ParseError::NotEvaluable`] if the
+    /// formula or its inverse uses constants which cannot be evaluated, such as `SCALE`.
+    pub fn exact_domain(
+        &self,
+        closure: &ExprClosure,
+        ty: &Type,
+        range: RangeInclusive<i128>,
+    ) -> Result<Vec<i128>, ParseError> {
+        let int_ty = eval::IntType::from_type(ty).ok_or_else(|| {
+            ParseError::Syntax(format!("`{}` is not an integer type", quote::quote!(#ty)))
+        })?;
+        let forward = Self::body(closure)?;
+        let inverse =
+            Self::new(self.solve_for.clone(), self.target_ident.clone()).solve(closure)?;
+        if !eval::is_evaluable(forward, &self.solve_for)
+            || !eval::is_evaluable(&inverse.body, &self.target_ident)
+        {
+            return Err(ParseError::NotEvaluable);
+        }
+        Ok(range
+            .filter(|&value| {
+                eval::eval(forward, &self.solve_for, value, int_ty).and_then(|output| {
+                    eval::eval(&inverse.body, &self.target_ident, output, int_ty)
+                }) == Some(value)
+            })
+            .collect())
+    }
+
+    /// Returns the fraction of the values of the integer type `ty` on which the inverse
+    /// overflows or divides by zero, or `None` if the inverse uses constants which cannot be
+    /// evaluated.
+    ///
+    /// For `|| a + u8::MAX` the inverse `x - u8::MAX` underflows for every `x` but 255. Wide
+    /// types are sampled at evenly spaced values rather than exhaustively.
+    pub fn overflow_fraction(
+        &self,
+        closure: &ExprClosure,
+        ty: &Type,
+    ) -> Result<Option<f64>, ParseError> {
+        const SAMPLES: i128 = 1024;
+        let int_ty = eval::IntType::from_type(ty).ok_or_else(|| {
+            ParseError::Syntax(format!("`{}` is not an integer type", quote::quote!(#ty)))
+        })?;
+        let inverse =
+            Self::new(self.solve_for.clone(), self.target_ident.clone()).solve(closure)?;
+        if !eval::is_evaluable(&inverse.body, &self.target_ident) {
+            return Ok(None);
+        }
+        let step = (int_ty.max / SAMPLES - int_ty.min / SAMPLES).max(1);
+        let samples: Vec<i128> = (0..)
+            .map_while(|i: i128| {
+                let value = int_ty.min.checked_add(step.checked_mul(i)?)?;
+                (value <= int_ty.max).then_some(value)
+            })
+            .collect();
+        let overflows = samples
+            .iter()
+            .filter(|&&value| {
+                eval::eval(&inverse.body, &self.target_ident, value, int_ty).is_none()
+            })
+            .count();
+        Ok(Some(overflows as f64 / samples.len() as f64))
+    }
+
+    /// Returns the constant operands of a closure body in evaluation order.
+    ///
+    /// Literals and any paths other than `solve_for` are treated as constants, so
+    /// `|| a * 9 / 5 + 32` gives `9, 5, 32`.
+    pub fn constants(&self, closure: &ExprClosure) -> Result<Vec<Expr>, ParseError> {
+        let body = Self::body(closure)?;
+        Self::validate_expr(body)?;
+        let mut constants = vec![];
+        self.collect_constants(body, &mut constants);
+        Ok(constants)
+    }
+
+    fn collect_constants(&self, e: &Expr, constants: &mut Vec<Expr>) {
+        match e {
+            Expr::Binary(b) => {
+                self.collect_constants(&b.left, constants);
+                self.collect_constants(&b.right, constants);
+            }
+            Expr::Path(p) if Self::parse_path(p, &self.solve_for) => {}
+            Expr::MethodCall(m) => self.collect_constants(&m.receiver, constants),
+            _ => constants.push(e.clone()),
+        }
+    }
+
+    /// Inverts a closure written as a string, e.g. `|| a * 2 + 1`, for `solve_for`.
+    ///
+    /// The returned closure takes a single parameter named `value`.
+    pub fn from_formula(solve_for: &str, formula: &str) -> Result<ExprClosure, ParseError> {
+        let solve_for = parse_ident(solve_for)?;
+        let closure = syn::parse_str::<ExprClosure>(formula)
+            .map_err(|e| ParseError::Syntax(e.to_string()))?;
+        ClosureInverter::new(solve_for, Ident::new("value", Span::call_site())).solve(&closure)
+    }
+
+    /// Attempts to invert every `(solve_for, formula)` pair, returning the index and error of
+    /// each one which fails.
+    pub fn validate_formulas(formulas: &[(&str, &str)]) -> Vec<(usize, ParseError)> {
+        formulas
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (solve_for, formula))| {
+                Self::from_formula(solve_for, formula).err().map(|e| (i, e))
+            })
+            .collect()
+    }
+
+    /// Undoes operations from the outside in until only the target path is left, passing the
+    /// observer a description of each operation with the inverse built so far.
+    fn parse_expr(&mut self, mut e: Expr, mut observer: Observer<'_>) -> Result<(), ParseError> {
+        let containing = Containing::new(&e, &self.solve_for);
+        loop {
+            let outer = observer
+                .is_some()
+                .then(|| format::format_expr(&e, Style::Pretty));
+            match self.peel(e, &containing)? {
+                Peeled::Step(inner) => {
+                    if let (Some(observer), Some(outer)) = (observer.as_deref_mut(), outer) {
+                        let inner_text = format::format_expr(&inner, Style::Pretty);
+                        observer(&outer.replacen(&inner_text, "_", 1), &self.target_expr);
+                    }
+                    e = inner;
+                }
+                Peeled::Unchanged(inner) => e = inner,
+                Peeled::Target => return Ok(()),
+            }
         }
     }
 
-    /// Recursive call which stops when Expr only contains the target path
-    fn parse_expr(&mut self, e: Expr) -> Result<(), ParseError> {
+    /// Takes the inverse built so far, to build the next step around it without a copy.
+    fn take_target_expr(&mut self) -> Box<Expr> {
+        std::mem::replace(
+            &mut self.target_expr,
+            Box::new(Expr::Verbatim(TokenStream::new())),
+        )
+    }
+
+    /// Undoes the outermost operation of `e`, returning what is left of it.
+    fn peel(&mut self, e: Expr, containing: &Containing) -> Result<Peeled, ParseError> {
         let e_span = e.span();
         match e {
+            // With disjoint bits, or-ing in a mask is undone by clearing it
+            Expr::Binary(b) if matches!(b.op, BinOp::BitOr(_)) => {
+                if !self.disjoint {
+                    return Err(ParseError::NonInvertibleOp(
+                        "`|` without disjoint bits".to_owned(),
+                        b.op.span(),
+                    ));
+                }
+                let left = containing.contains(&b.left);
+                let right = containing.contains(&b.right);
+                let (target, mask) = match (left, right) {
+                    (true, false) => (b.left, b.right),
+                    (false, true) => (b.right, b.left),
+                    (true, true) => return Err(ParseError::Multiple(e_span)),
+                    (false, false) => return Err(ParseError::NoSolveFor),
+                };
+                let target_expr = self.take_target_expr();
+                let cleared = Box::new(Expr::Unary(ExprUnary {
+                    attrs: vec![],
+                    op: UnOp::Not(Token![!](e_span)),
+                    expr: Self::parenthesize(mask, &BinOp::BitAnd(Token![&](e_span)))?,
+                }));
+                self.target_expr =
+                    Self::build_expr_binary(target_expr, BinOp::BitAnd(Token![&](e_span)), cleared);
+                Ok(Peeled::Step(*target))
+            }
             Expr::Binary(b) => {
-                let left = Self::check_contains_target(&b.left, &self.solve_for);
-                let right = Self::check_contains_target(&b.right, &self.solve_for);
+                let left = containing.contains(&b.left);
+                let right = containing.contains(&b.right);
                 let inverted_op = inverse_bin_op(&b.op, &e_span)?;
+                // Multiplying by zero leaves the inverse dividing by it
+                let constant = match (left, right, &b.op) {
+                    (true, false, BinOp::Mul(_) | BinOp::Div(_)) => Some(&b.right),
+                    (false, true, BinOp::Mul(_)) => Some(&b.left),
+                    _ => None,
+                };
+                if let Some(constant) = constant.filter(|c| is_zero_literal(c)) {
+                    return Err(ParseError::DivideByZero(constant.span()));
+                }
 
                 // Parenthesize expression
-                let target_expr = &self.target_expr;
                 match (left, right) {
                     (true, false) => {
+                        let target_expr = self.take_target_expr();
                         self.target_expr = Self::build_expr_binary(
                             Self::parenthesize(target_expr, &inverted_op)?,
                             inverted_op,
-                            b.right.clone(),
+                            b.right,
                         );
-                        self.parse_expr(*b.left)
+                        Ok(Peeled::Step(*b.left))
                     }
                     (false, true) => match &b.op {
                         BinOp::Add(_) | BinOp::Mul(_) => {
+                            let target_expr = self.take_target_expr();
                             self.target_expr = Self::build_expr_binary(
                                 Self::parenthesize(target_expr, &inverted_op)?,
                                 inverted_op,
-                                b.left.clone(),
+                                b.left,
                             );
-                            self.parse_expr(*b.right)
+                            Ok(Peeled::Step(*b.right))
                         }
                         BinOp::Sub(_) | BinOp::Div(_) => {
+                            let target_expr = self.take_target_expr();
                             self.target_expr = Self::build_expr_binary(
-                                b.left.clone(),
+                                b.left,
                                 b.op,
-                                Self::parenthesize(target_expr, &b.op)?,
+                                Self::parenthesize_right(target_expr, &b.op)?,
                             );
-                            self.parse_expr(*b.right)
+                            Ok(Peeled::Step(*b.right))
                         }
-                        _ => Err(ParseError::BinOp),
+                        BinOp::Shl(_) | BinOp::Shr(_) => Err(ParseError::NonInvertibleOp(
+                            "a shift by the target".to_owned(),
+                            e_span,
+                        )),
+                        op => Err(ParseError::BinOp(op.span())),
                     },
-                    (true, true) => Err(ParseError::Multiple),
+                    (true, true)
+                        if matches!(b.op, BinOp::Div(_))
+                            && strip_parens(&b.left) == strip_parens(&b.right) =>
+                    {
+                        Err(ParseError::TargetEliminated(
+                            "it is divided by itself".to_owned(),
+                        ))
+                    }
+                    (true, true) => Err(ParseError::Multiple(e_span)),
                     (false, false) => Err(ParseError::NoSolveFor),
                 }
             }
             Expr::Path(p) => {
                 if Self::parse_path(&p, &self.solve_for) {
-                    Ok(())
+                    Ok(Peeled::Target)
+                } else {
+                    Err(ParseError::UnexpectedIdentifier(e_span))
+                }
+            }
+            Expr::Lit(_) => Err(ParseError::NoSolveFor),
+            // Grouping is already reflected in the shape of the tree
+            Expr::Paren(mut p) => {
+                while let Expr::Paren(inner) = *p.expr {
+                    p = inner;
+                }
+                Ok(Peeled::Unchanged(*p.expr))
+            }
+            // An invisible group left by a macro expansion groups like parentheses
+            Expr::Group(g) => Ok(Peeled::Unchanged(*g.expr)),
+            // Cast back with `as _`, so the inverse takes the type expected where it is used
+            Expr::Cast(c) => {
+                if contains_cast(&c.expr) {
+                    return Err(ParseError::NonInvertibleOp(
+                        "a nested `as` cast".to_owned(),
+                        e_span,
+                    ));
+                }
+                let target_expr = self.take_target_expr();
+                *self.target_expr = Expr::Cast(ExprCast {
+                    attrs: vec![],
+                    expr: Self::parenthesize(target_expr, &BinOp::Mul(Token![*](e_span)))?,
+                    as_token: Token![as](e_span),
+                    ty: Box::new(Type::Infer(TypeInfer {
+                        underscore_token: Token![_](e_span),
+                    })),
+                });
+                Ok(Peeled::Step(*c.expr))
+            }
+            // Negation is its own inverse
+            Expr::Unary(u) if matches!(u.op, UnOp::Neg(_)) => {
+                let target_expr = self.take_target_expr();
+                *self.target_expr = Expr::Unary(ExprUnary {
+                    attrs: vec![],
+                    op: u.op,
+                    expr: Self::parenthesize(target_expr, &BinOp::Mul(Token![*](e_span)))?,
+                });
+                Ok(Peeled::Step(*u.expr))
+            }
+            Expr::Macro(_) => Err(ParseError::NonInvertibleOp(
+                "`const_expr!` containing the target".to_owned(),
+                e_span,
+            )),
+            Expr::MethodCall(m) if Self::is_abs(&m) => {
+                if self.non_negative {
+                    // Only the non-negative solution is wanted, so `abs` is the identity
+                    Ok(Peeled::Unchanged(*m.receiver))
                 } else {
-                    Err(ParseError::UnexpectedIdentifier)
+                    Err(ParseError::NonInvertibleOp(
+                        "`abs` without a non-negative domain".to_owned(),
+                        m.method.span(),
+                    ))
+                }
+            }
+            // The integer root, rounded down, found by binary search so that no type is needed
+            Expr::MethodCall(m) if Self::pow_exponent(&m).is_some() => {
+                let n = Self::pow_exponent(&m).ok_or(ParseError::Validation(e_span))?;
+                if n <= 1 {
+                    return Ok(Peeled::Unchanged(*m.receiver));
                 }
+                let target_expr = &self.target_expr;
+                self.target_expr = parse_quote!({
+                    let value = #target_expr;
+                    #[allow(clippy::eq_op)]
+                    let (mut low, mut high) = (value - value, value);
+                    while low < high {
+                        let mid = low + (high - low) / 2 + (high - low) % 2;
+                        if mid.checked_pow(#n).is_some_and(|power| power <= value) {
+                            low = mid;
+                        } else {
+                            high = mid - 1;
+                        }
+                    }
+                    low
+                });
+                Ok(Peeled::Step(*m.receiver))
             }
-            _ => unimplemented!(),
+            _ => Err(ParseError::Validation(e_span)),
         }
     }
 
@@ -136,10 +823,13 @@ impl ClosureInverter {
                     || Self::check_contains_target(&b.right, target)
             }
             Expr::Lit(_) => false,
-            Expr::Paren(_) => unimplemented!(),
+            Expr::Macro(m) => tokens_contain(m.mac.tokens.clone(), target),
+            Expr::MethodCall(m) => Self::check_contains_target(&m.receiver, target),
+            Expr::Paren(_) | Expr::Group(_) => Self::check_contains_target(strip_parens(e), target),
+            Expr::Cast(c) => Self::check_contains_target(&c.expr, target),
             Expr::Path(p) => Self::parse_path(p, target),
-            Expr::Unary(_) => unimplemented!(),
-            _ => unimplemented!(),
+            Expr::Unary(u) => Self::check_contains_target(&u.expr, target),
+            e => tokens_contain(e.to_token_stream(), target),
         }
     }
 
@@ -152,34 +842,189 @@ impl ClosureInverter {
     }
 
     // Adds parentheses if required
-    fn parenthesize(e: &Expr, target_op: &BinOp) -> Result<Box<Expr>, ParseError> {
-        match e {
-            Expr::Lit(_) | Expr::Path(_) => Ok(Box::new(e.clone())),
+    fn parenthesize(e: Box<Expr>, target_op: &BinOp) -> Result<Box<Expr>, ParseError> {
+        match &*e {
+            Expr::Lit(_) | Expr::Path(_) => Ok(e),
+            // `&` and shifts bind more loosely than arithmetic
+            Expr::Binary(b) if matches!(b.op, BinOp::BitAnd(_) | BinOp::Shl(_) | BinOp::Shr(_)) => {
+                Ok(paren(e))
+            }
             _ => match target_op {
-                BinOp::Add(_) | BinOp::Sub(_) => Ok(Box::new(e.clone())),
-                BinOp::Mul(_) | BinOp::Div(_) => Ok(parse_quote!( (#e))),
-                _ => Err(ParseError::BinOp),
+                BinOp::Add(_) | BinOp::Sub(_) => Ok(e),
+                BinOp::Mul(_) | BinOp::Div(_) | BinOp::Shl(_) | BinOp::Shr(_) => Ok(paren(e)),
+                BinOp::BitAnd(_) => Ok(paren(e)),
+                _ => Err(ParseError::BinOp(target_op.span())),
             },
         }
     }
+
+    // Adds parentheses if required to the right operand, which unlike the left is grouped under
+    // any operator which is not associative, so `20 - (value - 3)` keeps its meaning
+    fn parenthesize_right(e: Box<Expr>, target_op: &BinOp) -> Result<Box<Expr>, ParseError> {
+        match (&*e, target_op) {
+            (Expr::Lit(_) | Expr::Path(_) | Expr::Paren(_), _) => Ok(e),
+            (_, BinOp::Sub(_) | BinOp::Div(_)) => Ok(paren(e)),
+            _ => Self::parenthesize(e, target_op),
+        }
+    }
+}
+
+/// Inverts a closure written as a string for `solve_for`, returning the inverse as source code.
+///
+/// ```
+/// let inverse = proc_lineq::invert_str("|| a * 2 + 1", "a").unwrap();
+/// assert_eq!(inverse, "| value | (value - 1) / 2");
+/// ```
+#[cfg(feature = "solver")]
+pub fn invert_str(input: &str, solve_for: &str) -> Result<String, ParseError> {
+    let inverse = ClosureInverter::from_formula(solve_for, input)?;
+    Ok(quote::quote!(#inverse).to_string())
+}
+
+/// Inverts a closure written as a string for `solve_for` like [`invert_str`], spacing the
+/// operators of the inverse in the given style.
+#[cfg(feature = "solver")]
+pub fn invert_str_styled(input: &str, solve_for: &str, style: Style) -> Result<String, ParseError> {
+    let inverse = ClosureInverter::from_formula(solve_for, input)?;
+    Ok(format_closure(&inverse, style))
+}
+
+/// Returns true for the `const_expr!(...)` marker, whose contents are treated as an opaque
+/// constant which must not contain the target.
+#[cfg(feature = "solver")]
+fn is_const_expr(m: &ExprMacro) -> bool {
+    m.mac.path.is_ident("const_expr")
+}
+
+/// Returns true for the operators comparing two values, such as `==` and `<`.
+#[cfg(feature = "solver")]
+fn is_comparison(op: &BinOp) -> bool {
+    matches!(
+        op,
+        BinOp::Eq(_) | BinOp::Ne(_) | BinOp::Lt(_) | BinOp::Le(_) | BinOp::Gt(_) | BinOp::Ge(_)
+    )
+}
+
+/// Wraps an expression in parentheses.
+#[cfg(feature = "solver")]
+fn paren(e: Box<Expr>) -> Box<Expr> {
+    Box::new(Expr::Paren(ExprParen {
+        attrs: vec![],
+        paren_token: Default::default(),
+        expr: e,
+    }))
+}
+
+/// Returns the expression inside any number of grouping parentheses or invisible groups.
+#[cfg(feature = "solver")]
+fn strip_parens(mut e: &Expr) -> &Expr {
+    loop {
+        match e {
+            Expr::Paren(p) => e = &p.expr,
+            Expr::Group(g) => e = &g.expr,
+            e => return e,
+        }
+    }
+}
+
+/// Returns true if the expression is an integer or float literal zero.
+#[cfg(feature = "solver")]
+fn is_zero_literal(e: &Expr) -> bool {
+    match strip_parens(e) {
+        Expr::Lit(ExprLit {
+            lit: Lit::Int(i), ..
+        }) => matches!(i.base10_parse::<u128>(), Ok(0)),
+        Expr::Lit(ExprLit {
+            lit: Lit::Float(f), ..
+        }) => matches!(f.base10_parse::<f64>(), Ok(v) if v == 0.0),
+        _ => false,
+    }
+}
+
+#[cfg(feature = "solver")]
+fn contains_cast(e: &Expr) -> bool {
+    match e {
+        Expr::Binary(b) => contains_cast(&b.left) || contains_cast(&b.right),
+        Expr::Cast(_) => true,
+        Expr::MethodCall(m) => contains_cast(&m.receiver),
+        Expr::Paren(p) => contains_cast(&p.expr),
+        Expr::Group(g) => contains_cast(&g.expr),
+        Expr::Unary(u) => contains_cast(&u.expr),
+        _ => false,
+    }
+}
+
+#[cfg(feature = "solver")]
+fn tokens_contain(tokens: TokenStream, ident: &Ident) -> bool {
+    tokens_count(tokens, ident) > 0
+}
+
+/// Counts the occurrences of `ident` in `tokens` as a variable, so not those following `::` or
+/// `.` as in `Self::a` or `x.a`.
+#[cfg(feature = "solver")]
+fn tokens_count(tokens: TokenStream, ident: &Ident) -> usize {
+    let mut count = 0;
+    let mut qualified = false;
+    for token in tokens {
+        match &token {
+            TokenTree::Ident(i) => count += usize::from(i == ident && !qualified),
+            TokenTree::Group(g) => count += tokens_count(g.stream(), ident),
+            TokenTree::Punct(_) | TokenTree::Literal(_) => {}
+        }
+        qualified =
+            matches!(&token, TokenTree::Punct(p) if p.as_char() == ':' || p.as_char() == '.');
+    }
+    count
+}
+
+/// Parses the name of a variable.
+#[cfg(feature = "solver")]
+fn parse_ident(s: &str) -> Result<Ident, ParseError> {
+    syn::parse_str(s).map_err(|_| ParseError::Syntax(format!("`{}` is not an identifier", s)))
 }
 
+#[cfg(feature = "solver")]
 fn inverse_bin_op(op: &BinOp, dummy_span: &Span) -> Result<BinOp, ParseError> {
     match op {
         BinOp::Add(_) => Ok(BinOp::Sub(Token![-](*dummy_span))),
         BinOp::Sub(_) => Ok(BinOp::Add(Token![+](*dummy_span))),
         BinOp::Mul(_) => Ok(BinOp::Div(Token![/](*dummy_span))),
         BinOp::Div(_) => Ok(BinOp::Mul(Token![*](*dummy_span))),
-        _ => Err(ParseError::BinOp),
+        // Shifts multiply and divide by powers of two, so shifting back to the right loses
+        // the low bits just as division does
+        BinOp::Shl(_) => Ok(BinOp::Shr(Token![>>](*dummy_span))),
+        BinOp::Shr(_) => Ok(BinOp::Shl(Token![<<](*dummy_span))),
+        // Many values share each remainder, so there is nothing to solve for
+        BinOp::Rem(_) => Err(ParseError::NonInvertibleOp(
+            "the modulo operator".to_owned(),
+            op.span(),
+        )),
+        _ => Err(ParseError::BinOp(op.span())),
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "solver"))]
 mod tests {
+    use super::Invertible;
     use proc_lineq_derive::ClosureInverter;
+    use quote::format_ident;
+    use syn::{parse_quote, Expr, ExprClosure};
 
     // All tests currently test usize types only. Can be expanded in the future.
 
+    #[derive(ClosureInverter)]
+    #[invert(expr = "|| a / 2 + 7", gen_test = "roundtrip_halved")]
+    struct Halved;
+
+    #[derive(ClosureInverter)]
+    #[invert(
+        expr = "|| a * 2 + 10",
+        option_input = true,
+        variants = ["plain", "checked"],
+        gen_test = "roundtrip_optional"
+    )]
+    struct Optional;
+
     #[test]
     fn invert_basic_addition() {
         #[derive(ClosureInverter)]
@@ -240,6 +1085,264 @@ mod tests {
         assert_eq!(Test2::calculate(10), 50);
     }
 
+    #[test]
+    fn invert_target_as_divisor_with_offset() {
+        #[derive(ClosureInverter)]
+        #[invert("|| 100 / a + 5")]
+        struct Test;
+        assert_eq!(Test::calculate(25), 5);
+        assert_eq!(Test::calculate(7), 50);
+
+        #[derive(ClosureInverter)]
+        #[invert("|| 100 / a - 5")]
+        struct Test2;
+        assert_eq!(Test2::calculate(15), 5);
+        assert_eq!(Test2::calculate(45), 2);
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let inverse = eq.solve(&parse_quote!(|| 100 / a + 5)).unwrap();
+        assert_eq!(*inverse.body, parse_quote!(100 / (b - 5)));
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let inverse = eq.solve(&parse_quote!(|| 100 / a - 5)).unwrap();
+        assert_eq!(*inverse.body, parse_quote!(100 / (b + 5)));
+    }
+
+    #[test]
+    fn invert_coefficient_then_division() {
+        // Both orderings multiply before dividing, so each inverts to `(b * 2) / 3`. The forward
+        // division truncates, so only outputs of even inputs are recovered exactly: 4 comes from
+        // 3, but inverts to 2.
+        #[derive(ClosureInverter)]
+        #[invert("|| 3 * a / 2")]
+        struct Leading;
+        assert_eq!(Leading::calculate(6), 4);
+        assert_eq!(Leading::calculate(9), 6);
+        assert_eq!(Leading::calculate(4), 2);
+
+        #[derive(ClosureInverter)]
+        #[invert("|| a * 3 / 2")]
+        struct Trailing;
+        assert_eq!(Trailing::calculate(6), 4);
+        assert_eq!(Trailing::calculate(9), 6);
+        assert_eq!(Trailing::calculate(4), 2);
+
+        for closure in [parse_quote!(|| 3 * a / 2), parse_quote!(|| a * 3 / 2)] {
+            let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+            let inverse = eq.solve(&closure).unwrap();
+            assert_eq!(*inverse.body, parse_quote!((b * 2) / 3));
+        }
+    }
+
+    #[test]
+    fn invert_grouped_subtraction() {
+        // `a - (2 - 3)` is `a + 1`, whereas `a - 2 - 3` is `a - 5`
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a - (2 - 3)", ty = "i32")]
+        struct Grouped;
+        assert_eq!(Grouped::calculate(10), 9);
+        assert_eq!(Grouped::calculate(1), 0);
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a - 2 - 3", ty = "i32")]
+        struct Ungrouped;
+        assert_eq!(Ungrouped::calculate(10), 15);
+        assert_eq!(Ungrouped::calculate(-5), 0);
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let inverse = eq.solve(&parse_quote!(|| a - (2 - 3))).unwrap();
+        assert_eq!(*inverse.body, parse_quote!(b + (2 - 3)));
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let inverse = eq.solve(&parse_quote!(|| a - 2 - 3)).unwrap();
+        assert_eq!(*inverse.body, parse_quote!(b + 5));
+    }
+
+    #[test]
+    fn invert_exact_division() {
+        use crate::InversionError;
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| (a * 3 + 1) * 2", exact = true)]
+        struct Test;
+        assert_eq!(Test::calculate(14), Ok(2));
+        assert_eq!(
+            Test::calculate(9),
+            Err(InversionError::NotExact {
+                value: 9,
+                divisor: 2
+            })
+        );
+        let error = Test::calculate(10).unwrap_err();
+        assert_eq!(
+            error,
+            InversionError::NotExact {
+                value: 4,
+                divisor: 3
+            }
+        );
+        assert_eq!(error.to_string(), "4 is not divisible by 3");
+    }
+
+    #[test]
+    fn invert_by_reference() {
+        use std::ops::{Div, Sub};
+
+        /// A number which is not `Copy`, as an arbitrary-precision integer would be
+        #[derive(Clone, Debug, PartialEq)]
+        struct Big(Vec<i64>);
+
+        impl Sub<i64> for Big {
+            type Output = Big;
+
+            fn sub(self, rhs: i64) -> Big {
+                Big(self.0.into_iter().map(|digit| digit - rhs).collect())
+            }
+        }
+
+        impl Div<i64> for Big {
+            type Output = Big;
+
+            fn div(self, rhs: i64) -> Big {
+                Big(self.0.into_iter().map(|digit| digit / rhs).collect())
+            }
+        }
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a * 3 + 7", ty = "Big", deref_input = true)]
+        struct Test;
+        let value = Big(vec![10, 13]);
+        assert_eq!(Test::calculate(&value), Big(vec![1, 2]));
+        assert_eq!(value, Big(vec![10, 13]));
+    }
+
+    #[test]
+    fn invert_with_type_key() {
+        #[derive(ClosureInverter)]
+        #[invert("|| a * 2", type = "i64")]
+        struct Doubled;
+        let inverse: fn(i64) -> i64 = Doubled::calculate;
+        assert_eq!(inverse(-8), -4);
+        assert_eq!(inverse(-7), -3);
+
+        #[derive(ClosureInverter)]
+        #[invert("|| a / 4 - 3", type = "i64")]
+        struct Quartered;
+        assert_eq!(Quartered::calculate(-5), -8);
+        assert_eq!(Quartered::calculate(2), 20);
+    }
+
+    #[test]
+    fn invert_negated_target() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| -a + 3", ty = "i32")]
+        struct Negated;
+        assert_eq!(Negated::calculate(-4), 7);
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| 3 + -2 * a", ty = "i32")]
+        struct NegativeCoefficient;
+        assert_eq!(NegativeCoefficient::calculate(-7), 5);
+
+        let inverse = super::ClosureInverter::from_formula("a", "|| -a").unwrap();
+        assert_eq!(inverse, parse_quote!(|value| -value));
+    }
+
+    #[test]
+    fn invert_negation_with_division() {
+        // `-a / 2` negates before dividing and `-(a / 2)` after, which truncation makes equal
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| -a / 2", ty = "i32")]
+        struct NegatedFirst;
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| -(a / 2)", ty = "i32")]
+        struct NegatedLast;
+        for b in [-7, -4, 0, 3, 10] {
+            assert_eq!(NegatedFirst::calculate(b), -(b * 2));
+            assert_eq!(NegatedLast::calculate(b), NegatedFirst::calculate(b));
+        }
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let inverse = eq.solve(&parse_quote!(|| -a / 2)).unwrap();
+        assert_eq!(*inverse.body, parse_quote!(-(b * 2)));
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let inverse = eq.solve(&parse_quote!(|| -(a / 2))).unwrap();
+        assert_eq!(*inverse.body, parse_quote!((-b) * 2));
+    }
+
+    #[test]
+    fn invert_target_as_divisor_under_subtraction() {
+        // The forward division truncates, so only small divisors are recovered exactly: 30 gives
+        // `50 - 3`, which inverts to `100 / 3`, or 33.
+        #[derive(ClosureInverter)]
+        #[invert("|| 50 - 100 / a")]
+        struct Test;
+        assert_eq!(Test::calculate(17), 3);
+        assert_eq!(Test::calculate(36), 7);
+        assert_eq!(Test::calculate(47), 33);
+
+        #[derive(ClosureInverter)]
+        #[invert("|| 50 + 100 / a")]
+        struct Test2;
+        assert_eq!(Test2::calculate(83), 3);
+        assert_eq!(Test2::calculate(70), 5);
+        assert_eq!(Test2::calculate(53), 33);
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let inverse = eq.solve(&parse_quote!(|| 50 - 100 / a)).unwrap();
+        assert_eq!(*inverse.body, parse_quote!(100 / (50 - b)));
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let inverse = eq.solve(&parse_quote!(|| 50 + 100 / a)).unwrap();
+        assert_eq!(*inverse.body, parse_quote!(100 / (b - 50)));
+    }
+
+    #[test]
+    fn invert_redundant_parentheses() {
+        #[derive(ClosureInverter)]
+        #[invert("|| (((a * 3)))")]
+        struct Test;
+        assert_eq!(Test::calculate(12), 4);
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let inverse = eq.solve(&parse_quote!(|| (a * 3))).unwrap();
+        assert_eq!(*inverse.body, parse_quote!(b / 3));
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let inverse = eq.solve(&parse_quote!(|| (a + 2) * 4)).unwrap();
+        assert_eq!(*inverse.body, parse_quote!(b / 4 - 2));
+    }
+
+    #[test]
+    fn invert_grouped_constants() {
+        #[derive(ClosureInverter)]
+        #[invert("|| a - (10 + 20 + 30)")]
+        struct Subtracted;
+        assert_eq!(Subtracted::calculate(100), 160);
+
+        for (formula, inverse) in [
+            ("|| a - (10 + 20 + 30)", parse_quote!(|value| value + 60)),
+            ("|| a + (10 + 20)", parse_quote!(|value| value - 30)),
+            ("|| (10 + 20) + a", parse_quote!(|value| value - 30)),
+            ("|| (10 + 20) - a", parse_quote!(|value| 30 - value)),
+        ] {
+            let solved: ExprClosure = super::ClosureInverter::from_formula("a", formula).unwrap();
+            assert_eq!(solved, inverse, "{}", formula);
+        }
+    }
+
+    #[test]
+    fn invert_grouped_sum() {
+        #[derive(ClosureInverter)]
+        #[invert("|| (a + 1) * 2")]
+        struct Test;
+        assert_eq!(Test::calculate(8), 3);
+
+        let inverse = super::ClosureInverter::from_formula("a", "|| (a + 1) * 2").unwrap();
+        assert_eq!(inverse, parse_quote!(|value| value / 2 - 1));
+    }
+
     #[test]
     fn invert_complex_operators() {
         #[derive(ClosureInverter)]
@@ -265,15 +1368,15 @@ mod tests {
         #[invert("|| 200 - a * 2 + 3 * 2")]
         struct TestComplex4;
 
-        assert_eq!(TestComplex4::calculate(20), 87);
-        assert_eq!(TestComplex4::calculate(1), 96);
+        assert_eq!(TestComplex4::calculate(20), 93);
+        assert_eq!(TestComplex4::calculate(10), 98);
 
         #[derive(ClosureInverter)]
         #[invert("|| 10 - 2 * a + 4 / 2")]
         struct TestComplex5;
 
-        assert_eq!(TestComplex5::calculate(2), 3);
-        assert_eq!(TestComplex5::calculate(1), 3);
+        assert_eq!(TestComplex5::calculate(2), 5);
+        assert_eq!(TestComplex5::calculate(4), 4);
 
         #[derive(ClosureInverter)]
         #[invert("|| 33 + 4 * 2 - 100 / a")]
@@ -282,4 +1385,1513 @@ mod tests {
         assert_eq!(TestComplex6::calculate(21), 5);
         assert_eq!(TestComplex6::calculate(31), 10);
     }
+
+    #[test]
+    fn debug_bounds_accepts_in_domain() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| 100 / a", debug_bounds = true)]
+        struct Test;
+        assert_eq!(Test::calculate(5), 20);
+        assert_eq!(Test::calculate(100), 1);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "outside the domain")]
+    fn debug_bounds_rejects_out_of_domain() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| 100 / a", debug_bounds = true)]
+        struct Test;
+        Test::calculate(0);
+    }
+
+    #[test]
+    fn saturate_domain_clamps_input() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a * 2 + 10", domain = "10..=210", saturate_domain = true)]
+        struct Test;
+        // At the bounds
+        assert_eq!(Test::calculate(10), 0);
+        assert_eq!(Test::calculate(210), 100);
+        // Below and above
+        assert_eq!(Test::calculate(0), 0);
+        assert_eq!(Test::calculate(500), 100);
+        // Inside
+        assert_eq!(Test::calculate(50), 20);
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a + 2", domain = "2..12", saturate_domain = true)]
+        struct TestHalfOpen;
+        assert_eq!(TestHalfOpen::calculate(1), 0);
+        assert_eq!(TestHalfOpen::calculate(11), 9);
+        assert_eq!(TestHalfOpen::calculate(12), 9);
+    }
+
+    #[test]
+    fn half_open_float_domain() {
+        #[derive(ClosureInverter)]
+        #[invert(
+            expr = "|| a * 2.0",
+            ty = "f64",
+            domain = "0.0..10.0",
+            debug_bounds = true
+        )]
+        struct Test;
+        assert_eq!(Test::calculate(0.0), 0.0);
+        assert_eq!(Test::calculate(9.5), 4.75);
+
+        #[derive(ClosureInverter)]
+        #[invert(
+            expr = "|| a * 2.0",
+            ty = "f64",
+            domain = "0.0..=10.0",
+            saturate_domain = true
+        )]
+        struct TestSaturated;
+        assert_eq!(TestSaturated::calculate(12.0), 5.0);
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "outside the declared domain")]
+    fn half_open_float_domain_excludes_end() {
+        #[derive(ClosureInverter)]
+        #[invert(
+            expr = "|| a * 2.0",
+            ty = "f64",
+            domain = "0.0..10.0",
+            debug_bounds = true
+        )]
+        struct Test;
+        Test::calculate(10.0);
+    }
+
+    #[test]
+    fn invert_with_let_bindings() {
+        #[derive(ClosureInverter)]
+        #[invert(let = "SCALE = 3", let = "OFFSET = 7", expr = "|| a * SCALE + OFFSET")]
+        struct Test;
+        assert_eq!(Test::calculate(10), 1);
+        assert_eq!(Test::calculate(37), 10);
+
+        #[derive(ClosureInverter)]
+        #[invert(let = "BASE = 4", let = "STEP = BASE * 2", expr = "|| a * STEP - BASE")]
+        struct TestChained;
+        assert_eq!(TestChained::calculate(12), 2);
+    }
+
+    #[test]
+    fn invert_explicit_return() {
+        #[derive(ClosureInverter)]
+        #[invert("|| { return a * 3; }")]
+        struct Test;
+        assert_eq!(Test::calculate(9), 3);
+        assert_eq!(Test::calculate(30), 10);
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let closure: ExprClosure = parse_quote!(|| {
+            return a * 3;
+            return a;
+        });
+        assert!(matches!(
+            eq.solve(&closure),
+            Err(super::ParseError::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn invert_negative_literals() {
+        for (formula, expected) in [
+            ("|| a + -2", "| value | value + 2"),
+            ("|| -2 + a", "| value | value + 2"),
+            ("|| a - -2", "| value | value - 2"),
+            ("|| (a + -2) * 3", "| value | value / 3 + 2"),
+        ] {
+            assert_eq!(
+                super::invert_str(formula, "a").unwrap(),
+                expected,
+                "{}",
+                formula
+            );
+        }
+
+        #[derive(ClosureInverter)]
+        #[invert("|| a * 2 + -4")]
+        struct Offset;
+        assert_eq!(Offset::calculate(6), 5);
+    }
+
+    #[test]
+    fn invert_suffixed_literals() {
+        #[derive(ClosureInverter)]
+        #[invert("|| a * 2u32 + 1")]
+        struct Inferred;
+        let value: u32 = 7;
+        assert_eq!(Inferred::calculate(value), 3);
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a * 2i64 - 4", ty = "i64")]
+        struct Declared;
+        assert_eq!(Declared::calculate(-10), -3);
+
+        // The exponent of `pow` is always a `u32`
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a.pow(2u32) * 2", ty = "u64")]
+        struct Squared;
+        assert_eq!(Squared::calculate(18), 3);
+    }
+
+    #[test]
+    fn invert_deeply_nested() {
+        let mut formula = "a".to_owned();
+        let mut expected = "value".to_owned();
+        for _ in 0..50 {
+            formula = format!("({} * 3 + 1)", formula);
+            expected = format!("(({}) - 1) / 3", expected);
+        }
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("value"));
+        let closure: ExprClosure = syn::parse_str(&format!("|| {}", formula)).unwrap();
+        let inverse = eq.solve(&closure).unwrap();
+        let expected: Expr = syn::parse_str(&expected).unwrap();
+        assert_eq!(
+            super::fold::canonical(
+                &inverse.body,
+                &format_ident!("value"),
+                &format_ident!("value")
+            ),
+            super::fold::canonical(&expected, &format_ident!("value"), &format_ident!("value"))
+        );
+    }
+
+    #[test]
+    fn invert_block_body() {
+        #[derive(ClosureInverter)]
+        #[invert("|| { a * 3 + 1 }")]
+        struct Test;
+        assert_eq!(Test::calculate(10), 3);
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let inverse = eq.solve(&parse_quote!(|| { (a + 2) * 5 })).unwrap();
+        assert_eq!(*inverse.body, parse_quote!(b / 5 - 2));
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let error = eq
+            .solve(&parse_quote!(|| {
+                let b = a * 2;
+                b + 1
+            }))
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "could not parse formula: a block must hold a single expression"
+        );
+    }
+
+    #[test]
+    fn generated_roundtrip_test_passes() {
+        roundtrip_halved();
+        assert_eq!(Halved::calculate(12), 10);
+    }
+
+    #[test]
+    fn invert_folded_multiply_divide() {
+        // Folded to `a * 2`, so the inverse is `b / 2`
+        #[derive(ClosureInverter)]
+        #[invert("|| a * 6 / 3")]
+        struct Test;
+        assert_eq!(Test::calculate(10), 5);
+        assert_eq!(Test::calculate(11), 5);
+
+        // 2 does not divide 7, so the inverse is `b * 2 / 7` which truncates
+        #[derive(ClosureInverter)]
+        #[invert("|| a * 7 / 2")]
+        struct TestInexact;
+        assert_eq!(TestInexact::calculate(10), 2);
+        assert_eq!(TestInexact::calculate(14), 4);
+    }
+
+    #[test]
+    fn invert_with_extra_bounds() {
+        #[derive(ClosureInverter)]
+        #[invert(
+            expr = "|| a - 5",
+            ty = "i64",
+            extra_bounds = "i64: core::fmt::Display, i64: Copy"
+        )]
+        struct Test;
+        assert_eq!(Test::calculate(-3), 2);
+    }
+
+    #[test]
+    fn detect_involution() {
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert!(eq.is_involution(&parse_quote!(|| 100 - a)).unwrap());
+        assert!(!eq.is_involution(&parse_quote!(|| a + 2)).unwrap());
+        assert!(!eq.is_involution(&parse_quote!(|| 100 / a + 1)).unwrap());
+        assert!(eq.is_involution(&parse_quote!(|| 100 / a)).unwrap());
+    }
+
+    #[test]
+    fn invert_enum_variant() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a * 2 + 1", variant = "Raw")]
+        enum Reading {
+            Raw(usize),
+            Missing,
+        }
+        assert_eq!(Reading::Raw(7).calculate(), Some(3));
+        assert_eq!(Reading::Missing.calculate(), None);
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a + 2", variant = "Only")]
+        enum Single {
+            Only(usize),
+        }
+        assert_eq!(Single::Only(7).calculate(), Some(5));
+    }
+
+    #[test]
+    fn coerce_float_literals() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a * 2.0 + 1.9", ty = "u32", coerce_literals = true)]
+        struct Test;
+        let result: u32 = Test::calculate(9);
+        assert_eq!(result, 4);
+    }
+
+    #[test]
+    fn invert_pipeline() {
+        #[derive(ClosureInverter)]
+        #[invert(pipeline = ["|| a * 2", "|| a + 3"])]
+        struct Test;
+
+        #[derive(ClosureInverter)]
+        #[invert("|| a * 2 + 3")]
+        struct TestComposed;
+
+        for value in [3, 5, 13, 103] {
+            assert_eq!(Test::calculate(value), TestComposed::calculate(value));
+        }
+
+        // Later stages apply to the whole of earlier ones
+        #[derive(ClosureInverter)]
+        #[invert(pipeline = ["|| a + 3", "|| a * 2", "|| a + 1"])]
+        struct TestGrouped;
+        assert_eq!(TestGrouped::calculate(11), 2);
+    }
+
+    #[test]
+    fn wrap_result_in_newtype() {
+        #[derive(Debug, PartialEq)]
+        struct Meters(u32);
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a * 3", ty = "u32", result_wrap = "Meters")]
+        struct Test;
+        let meters = Test::calculate(30);
+        assert_eq!(meters, Meters(10));
+        assert_eq!(meters.0, 10);
+    }
+
+    #[test]
+    fn invert_abs_with_non_negative_domain() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a.abs() + 1", ty = "i32", domain = "1..=100")]
+        struct Test;
+        assert_eq!(Test::calculate(5), 4);
+        assert_eq!(Test::calculate(1), 0);
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert!(matches!(
+            eq.solve(&parse_quote!(|| a.abs() + 1)),
+            Err(super::ParseError::NonInvertibleOp(..))
+        ));
+    }
+
+    #[test]
+    fn invert_long_additive_chain() {
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let inverse = eq.solve(&parse_quote!(|| 1 + 2 + 3 + a - 4 - 5)).unwrap();
+        assert_eq!(inverse, parse_quote!(|b| b + 3));
+
+        #[derive(ClosureInverter)]
+        #[invert("|| 1 + 2 + 3 + a - 4 - 5")]
+        struct Test;
+        assert_eq!(Test::calculate(7), 10);
+        assert_eq!(Test::calculate(0), 3);
+    }
+
+    #[test]
+    fn convert_uom_quantities() {
+        // Stands in for `uom::si::Quantity`, whose value is stored in base units. The `uom` crate
+        // itself is not a dev-dependency, so this is the only check of the generated conversions
+        use std::marker::PhantomData;
+        #[allow(dead_code)]
+        struct Quantity<D> {
+            dimension: PhantomData<D>,
+            units: PhantomData<()>,
+            value: f64,
+        }
+        struct Temperature;
+        struct TemperatureInterval;
+        type ThermodynamicTemperature = Quantity<Temperature>;
+        type TemperatureDifference = Quantity<TemperatureInterval>;
+
+        // Kelvin above freezing from an absolute temperature in kelvin
+        #[derive(ClosureInverter)]
+        #[invert(
+            expr = "|| a + 273.15",
+            ty = "f64",
+            uom_in = "ThermodynamicTemperature",
+            uom_out = "TemperatureDifference"
+        )]
+        struct AboveFreezing;
+
+        let boiling = ThermodynamicTemperature {
+            dimension: PhantomData,
+            units: PhantomData,
+            value: 373.15,
+        };
+        let difference: TemperatureDifference = AboveFreezing::calculate(boiling);
+        assert!((difference.value - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn assert_inverse_pairs() {
+        proc_lineq_derive::assert_inverses!(|| a + 2, |x| x - 2);
+        proc_lineq_derive::assert_inverses!(|| a * 3 + 1, |value| (value - 1) / 3);
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert!(eq
+            .is_inverse(&parse_quote!(|| 2 * a - 4), &parse_quote!(|y| (y + 4) / 2))
+            .unwrap());
+        assert!(!eq
+            .is_inverse(&parse_quote!(|| a + 2), &parse_quote!(|y| y + 2))
+            .unwrap());
+    }
+
+    #[test]
+    fn exact_domain_of_division() {
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let exact = eq
+            .exact_domain(&parse_quote!(|| a / 2), &parse_quote!(u8), 0..=9)
+            .unwrap();
+        assert_eq!(exact, [0, 2, 4, 6, 8]);
+
+        // Every input to a multiplication is recovered, until it overflows
+        let exact = eq
+            .exact_domain(&parse_quote!(|| a * 100), &parse_quote!(u8), 0..=9)
+            .unwrap();
+        assert_eq!(exact, [0, 1, 2]);
+
+        // A named constant has no value to evaluate with, rather than no exact inputs
+        assert!(matches!(
+            eq.exact_domain(&parse_quote!(|| a / SCALE), &parse_quote!(u8), 0..=9),
+            Err(super::ParseError::NotEvaluable)
+        ));
+    }
+
+    #[test]
+    fn invert_unicode_operators() {
+        #[derive(ClosureInverter)]
+        #[invert("|| a \u{2212} 2")]
+        struct TestMinus;
+        assert_eq!(TestMinus::calculate(5), 7);
+
+        #[derive(ClosureInverter)]
+        #[invert("|| a \u{00D7} 3")]
+        struct TestTimes;
+        assert_eq!(TestTimes::calculate(9), 3);
+
+        #[derive(ClosureInverter)]
+        #[invert("|| a \u{00F7} 2")]
+        struct TestDivide;
+        assert_eq!(TestDivide::calculate(5), 10);
+
+        #[derive(ClosureInverter)]
+        #[invert("||\u{00A0}a\u{2009}\u{00D7}\u{2009}3 \u{2212} 1")]
+        struct TestSpacing;
+        assert_eq!(TestSpacing::calculate(8), 3);
+    }
+
+    #[test]
+    fn invert_table() {
+        const TABLE: [usize; 8] = proc_lineq_derive::invert_table!(|| a * 2, 0..8);
+        assert_eq!(TABLE, [0, 0, 1, 1, 2, 2, 3, 3]);
+
+        let table = proc_lineq_derive::invert_table!(|| a - 3, 0..=3);
+        assert_eq!(table, [3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn invert_function_signature() {
+        #[proc_lineq_derive::invert("|| a * 3 + 2")]
+        fn decode(value: u32) -> u32;
+
+        /// Documented, public and const.
+        #[proc_lineq_derive::invert("|x| x - 10")]
+        pub const fn offset(raw: i64) -> i64;
+
+        assert_eq!(decode(14), 4);
+        assert_eq!(offset(-3), 7);
+        const OFFSET: i64 = offset(0);
+        assert_eq!(OFFSET, 10);
+    }
+
+    #[test]
+    fn invert_for_named_parameter() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|a, b| a * b + 2", solve_for = "a")]
+        struct SolveA;
+        assert_eq!(SolveA::calculate(14, 3), 4);
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|a, b| a * b + 2", solve_for = "b")]
+        struct SolveB;
+        assert_eq!(SolveB::calculate(14, 4), 3);
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|x, k| k - x", solve_for = "x")]
+        struct SolveX;
+        assert_eq!(SolveX::calculate(3, 10), 7);
+    }
+
+    #[test]
+    fn invert_for_bound_parameter() {
+        #[derive(ClosureInverter)]
+        #[invert("|x| x + 2")]
+        struct Bound;
+        assert_eq!(Bound::calculate(9), 7);
+
+        #[derive(ClosureInverter)]
+        #[invert("|v: usize| v * 3")]
+        struct Typed;
+        assert_eq!(Typed::calculate(12), 4);
+
+        let param = super::ClosureInverter::bound_param(&parse_quote!(|x| x + 2)).unwrap();
+        assert_eq!(param, Some(format_ident!("x")));
+        let param = super::ClosureInverter::bound_param(&parse_quote!(|| a + 2)).unwrap();
+        assert_eq!(param, None);
+        assert!(matches!(
+            super::ClosureInverter::bound_param(&parse_quote!(|a, b| a * b)),
+            Err(super::ParseError::AmbiguousTarget(2))
+        ));
+    }
+
+    fn scale() -> usize {
+        4
+    }
+
+    #[test]
+    fn invert_around_const_expr() {
+        #[derive(ClosureInverter)]
+        #[invert("|| a * const_expr!(scale()) + 1")]
+        struct Test;
+        assert_eq!(Test::calculate(13), 3);
+
+        #[proc_lineq_derive::invert("|| a * const_expr!(scale()) + 1")]
+        fn decode(value: usize) -> usize;
+        assert_eq!(decode(13), 3);
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert!(matches!(
+            eq.solve(&parse_quote!(|| const_expr!(a * 2) + 1)),
+            Err(super::ParseError::NonInvertibleOp(..))
+        ));
+    }
+
+    #[test]
+    fn invert_plain_and_checked() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a * 2 + 1", variants = ["plain", "checked"])]
+        struct Test;
+        assert_eq!(Test::calculate(7), 3);
+        assert_eq!(Test::calculate_checked(7), Some(3));
+        assert_eq!(Test::calculate_checked(0), None);
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| -a", ty = "i8", variants = ["checked"])]
+        struct Negated;
+        assert_eq!(Negated::calculate_checked(5), Some(-5));
+        assert_eq!(Negated::calculate_checked(i8::MIN), None);
+    }
+
+    #[test]
+    fn reject_unsolved_target_in_inverse() {
+        let mut eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert!(eq.check_solved().is_ok());
+
+        *eq.target_expr = parse_quote!(b - a);
+        assert!(matches!(
+            eq.check_solved(),
+            Err(super::ParseError::InternalInvariant)
+        ));
+    }
+
+    #[test]
+    fn invert_cfg_gated() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a + 1", cfg = "target_pointer_width = \"64\"")]
+        struct Wide;
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a + 2", cfg = "not(target_pointer_width = \"64\")")]
+        struct Narrow;
+        let _ = (Wide, Narrow);
+
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(Wide::calculate(5), 4);
+        #[cfg(not(target_pointer_width = "64"))]
+        assert_eq!(Narrow::calculate(5), 3);
+
+        // Gated out, so this does not conflict with a generated method
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a + 1", cfg = "any()")]
+        struct Test;
+        impl Test {
+            fn calculate(value: usize) -> usize {
+                value * 10
+            }
+        }
+        assert_eq!(Test::calculate(5), 50);
+    }
+
+    #[test]
+    fn invert_pow() {
+        #[derive(ClosureInverter)]
+        #[invert("|| a.pow(2) + 1")]
+        struct Squared;
+        assert_eq!(Squared::calculate(50), 7);
+        assert_eq!(Squared::calculate(1), 0);
+
+        // Inexact roots are rounded down
+        #[derive(ClosureInverter)]
+        #[invert("|| (a + 1).pow(3)")]
+        struct Cubed;
+        assert_eq!(Cubed::calculate(64), 3);
+        assert_eq!(Cubed::calculate(70), 3);
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a.pow(2)", ty = "u64")]
+        struct Wide;
+        assert_eq!(Wide::calculate(u64::MAX), u64::from(u32::MAX));
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert!(matches!(
+            eq.solve(&parse_quote!(|| a.pow(k))),
+            Err(super::ParseError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn observe_inversion_steps() {
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let mut steps = vec![];
+        let inverse = eq
+            .solve_observed(&parse_quote!(|| 100 - (a * 3 + 7)), &mut |step, inverse| {
+                steps.push((step.to_owned(), quote::quote!(#inverse).to_string()));
+            })
+            .unwrap();
+        assert_eq!(*inverse.body, parse_quote!((100 - b - 7) / 3));
+        assert_eq!(
+            steps,
+            [
+                ("100 - _".to_owned(), "100 - b".to_owned()),
+                ("_ + 7".to_owned(), "100 - b - 7".to_owned()),
+                ("_ * 3".to_owned(), "(100 - b - 7) / 3".to_owned()),
+            ]
+        );
+
+        // Removing grouping or an `abs` of a non-negative value takes no step
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b")).non_negative();
+        let mut steps = vec![];
+        eq.solve_observed(&parse_quote!(|| (a.abs() * 2)), &mut |step, _| {
+            steps.push(step.to_owned());
+        })
+        .unwrap();
+        assert_eq!(steps, ["_ * 2"]);
+    }
+
+    #[test]
+    fn invert_repeated_target() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| 2 * a + a + 1")]
+        struct Test;
+        assert_eq!(Test::calculate(10), 3);
+
+        let inverse = super::ClosureInverter::from_formula("a", "|| a + a").unwrap();
+        assert_eq!(inverse, parse_quote!(|value| value / 2));
+
+        let error = super::ClosureInverter::from_formula("a", "|| a * a + a").unwrap_err();
+        assert_eq!(error.to_string(), "formula is degree 2 in `a`");
+    }
+
+    #[test]
+    fn invert_float_formula() {
+        let inverse = super::ClosureInverter::from_formula("a", "|| a * 1.8 + 32.0").unwrap();
+        assert_eq!(inverse, parse_quote!(|value| (value - 32.0) / 1.8));
+
+        // Fahrenheit from Celsius, taken as `f64` for its float literal
+        #[derive(ClosureInverter)]
+        #[invert("|| a * 1.8 + 32")]
+        struct Celsius;
+        for celsius in [-40.0, 0.0, 37.0, 100.0] {
+            let fahrenheit = celsius * 1.8 + 32.0;
+            assert!((Celsius::calculate(fahrenheit) - celsius).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn apply_generic_inverse() {
+        fn apply<T: Invertible<Num = usize>>(values: &[usize]) -> Vec<usize> {
+            values.iter().map(|value| T::calculate(*value)).collect()
+        }
+
+        #[derive(ClosureInverter)]
+        #[invert("|| a * 3 + 1")]
+        struct Tripled;
+        assert_eq!(apply::<Tripled>(&[4, 7]), [1, 2]);
+        assert_eq!(apply::<Halved>(&[7, 12]), [0, 10]);
+    }
+
+    #[test]
+    fn generate_forward() {
+        #[derive(ClosureInverter)]
+        #[invert("|| a * 3 + 2", forward = true)]
+        struct Test;
+        assert_eq!(Test::forward(4), 14);
+        for y in [2, 5, 14, 29] {
+            assert_eq!(Test::forward(Test::calculate(y)), y);
+        }
+
+        #[derive(ClosureInverter)]
+        #[invert("|a, k| a * k + 1", solve_for = "a", forward = true)]
+        struct Scaled;
+        assert_eq!(Scaled::forward(4, 5), 21);
+        assert_eq!(Scaled::calculate(21, 5), 4);
+    }
+
+    #[test]
+    fn invert_with_free_parameters() {
+        #[derive(ClosureInverter)]
+        #[invert("|| a * k + b")]
+        struct Scaled;
+        assert_eq!(Scaled::calculate(23, 4, 3), 5);
+
+        // Constants are upper case, so are not taken as parameters
+        const OFFSET: usize = 1;
+        #[derive(ClosureInverter)]
+        #[invert("|| (a + OFFSET) * step")]
+        struct Stepped;
+        assert_eq!(Stepped::calculate(12, 3), 3);
+
+        // A parameter may share the name of the generated input
+        #[derive(ClosureInverter)]
+        #[invert("|| a * k + value", debug_bounds = true, variants = ["plain", "checked"])]
+        struct Offset;
+        assert_eq!(Offset::calculate(23, 4, 3), 5);
+        assert_eq!(Offset::calculate_checked(2, 4, 3), None);
+
+        #[derive(ClosureInverter)]
+        #[invert("|a, value| a * value + 1", solve_for = "a", exact = true)]
+        struct Named;
+        assert_eq!(Named::calculate(13, 3), Ok(4));
+        assert!(Named::calculate(12, 3).is_err());
+    }
+
+    #[test]
+    fn parenthesize_chained_subtractions() {
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert_eq!(
+            eq.solve_expr(&parse_quote!(20 - (10 - a)), format_ident!("y"))
+                .unwrap(),
+            parse_quote!(10 - (20 - y))
+        );
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert_eq!(
+            eq.solve_expr(&parse_quote!(7 - a - 2), format_ident!("y"))
+                .unwrap(),
+            parse_quote!(7 - (y + 2))
+        );
+
+        #[derive(ClosureInverter)]
+        #[invert("|| 20 - (10 - a)")]
+        struct Nested;
+        assert_eq!(Nested::calculate(15), 5);
+
+        #[derive(ClosureInverter)]
+        #[invert("|| 100 - (50 - (10 - a))")]
+        struct Twice;
+        assert_eq!(Twice::calculate(57), 3);
+
+        #[derive(ClosureInverter)]
+        #[invert("|| 120 / (60 / a)")]
+        struct Divided;
+        assert_eq!(Divided::calculate(6), 3);
+    }
+
+    #[test]
+    fn invert_shifts() {
+        assert_eq!(
+            super::invert_str("|| (a << 2) - 1", "a").unwrap(),
+            "| value | (value + 1) >> 2"
+        );
+
+        #[derive(ClosureInverter)]
+        #[invert("|| a << 3")]
+        struct Shifted;
+        assert_eq!(Shifted::calculate(40), 5);
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| (a >> 2) + 1", ty = "u8", variants = ["plain", "checked"])]
+        struct Halved;
+        assert_eq!(Halved::calculate(3), 8);
+        assert_eq!(Halved::calculate_checked(64), Some(252));
+        assert_eq!(Halved::calculate_checked(65), None);
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a << 2", exact = true)]
+        struct Exact;
+        assert_eq!(Exact::calculate(12), Ok(3));
+        assert_eq!(
+            Exact::calculate(13),
+            Err(crate::InversionError::NotExact {
+                value: 13,
+                divisor: 4
+            })
+        );
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let error = eq.solve(&parse_quote!(|| 1 << a)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "a shift by the target cannot be inverted"
+        );
+    }
+
+    #[test]
+    fn inspect_inverter() {
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b")).disjoint();
+        assert_eq!(eq.solve_for(), "a");
+        assert_eq!(eq.target_ident(), "b");
+        assert_eq!(
+            format!("{:?}", eq),
+            "ClosureInverter { solve_for: a, target_ident: b, target_expr: b, \
+             non_negative: false, disjoint: true }"
+        );
+    }
+
+    #[test]
+    fn invert_const_fn() {
+        #[derive(ClosureInverter)]
+        #[invert("|| a * 4 + 2", const_fn = true)]
+        struct Packed;
+        const LEN: usize = Packed::calculate(14);
+        let buffer = [0u8; LEN];
+        assert_eq!(buffer.len(), 3);
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| -a / 2", ty = "i32", const_fn = true, exact = true)]
+        struct Halved;
+        const HALF: Result<i32, crate::InversionError<i32>> = Halved::calculate(3);
+        assert_eq!(HALF, Ok(-6));
+    }
+
+    #[test]
+    fn solve_equation() {
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert_eq!(
+            eq.solve(&parse_quote!(|y| a + 2 == y)).unwrap(),
+            parse_quote!(|y| y - 2)
+        );
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert_eq!(
+            eq.solve(&parse_quote!(|y| y + 1 == 3 * a)).unwrap(),
+            parse_quote!(|y| (y + 1) / 3)
+        );
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let error = eq.solve(&parse_quote!(|y| a * 2 < y)).unwrap_err();
+        assert_eq!(error.to_string(), "the comparison `<` cannot be inverted");
+
+        #[derive(ClosureInverter)]
+        #[invert("|y| a * 2 + 3 == y")]
+        struct Equation;
+        assert_eq!(Equation::calculate(13), 5);
+
+        #[derive(ClosureInverter)]
+        #[invert("|y| y == 4 * a")]
+        struct Reversed;
+        assert_eq!(Reversed::calculate(12), 3);
+    }
+
+    #[test]
+    fn invert_grouped_expression() {
+        // `$e * 3` with `$e` matching `a + 2` groups the fragment invisibly
+        let group = Expr::Group(syn::ExprGroup {
+            attrs: vec![],
+            group_token: Default::default(),
+            expr: Box::new(parse_quote!(a + 2)),
+        });
+        let closure: ExprClosure = parse_quote!(|| #group * 3);
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert_eq!(eq.solve(&closure).unwrap(), parse_quote!(|b| b / 3 - 2));
+
+        let group = Expr::Group(syn::ExprGroup {
+            attrs: vec![],
+            group_token: Default::default(),
+            expr: Box::new(parse_quote!(-a)),
+        });
+        let closure: ExprClosure = parse_quote!(|| #group + 1);
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert_eq!(eq.solve(&closure).unwrap(), parse_quote!(|b| -(b - 1)));
+    }
+
+    #[test]
+    fn invert_bare_expression() {
+        #[derive(ClosureInverter)]
+        #[invert("a * 2 + 3")]
+        struct Bare;
+        assert_eq!(Bare::calculate(13), 5);
+
+        #[derive(ClosureInverter)]
+        #[invert(pipeline = ["a + 1", "|| a * 3"])]
+        struct Piped;
+        assert_eq!(Piped::calculate(15), 4);
+    }
+
+    #[test]
+    fn invert_associated_constants() {
+        #[derive(ClosureInverter)]
+        #[invert("|| a * Self::SCALE + Self::OFFSET")]
+        struct Test;
+        impl Test {
+            const SCALE: usize = 3;
+            const OFFSET: usize = 7;
+        }
+        assert_eq!(Test::calculate(22), 5);
+
+        let inverse =
+            super::ClosureInverter::from_formula("a", "|| a * Self::SCALE + units::OFFSET")
+                .unwrap();
+        assert_eq!(
+            inverse,
+            parse_quote!(|value| (value - units::OFFSET) / Self::SCALE)
+        );
+
+        // A constant sharing the target's name is not the target
+        let inverse = super::ClosureInverter::from_formula("a", "|| a * scale::a + 1").unwrap();
+        assert_eq!(inverse, parse_quote!(|value| (value - 1) / scale::a));
+    }
+
+    #[test]
+    fn verify_at_compile_time() {
+        #[derive(ClosureInverter)]
+        #[invert("|| a * 3 - 2", ty = "i32", verify = true)]
+        struct Verified;
+        assert_eq!(Verified::calculate(10), 4);
+
+        // `a - 5` overflows for the smaller inputs, which are skipped
+        #[derive(ClosureInverter)]
+        #[invert("|| (a - 5) * 2", ty = "u8", verify = true)]
+        struct Skipped;
+        assert_eq!(Skipped::calculate(4), 7);
+
+        // The check is outside the impl, so associated constants are named by the type
+        #[derive(ClosureInverter)]
+        #[invert("|| a * Scaled::SCALE + 1", verify = true)]
+        struct Scaled;
+        impl Scaled {
+            const SCALE: usize = 3;
+        }
+        assert_eq!(Scaled::calculate(13), 4);
+    }
+
+    #[test]
+    fn solve_to_string() {
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert_eq!(
+            eq.solve_to_string(&parse_quote!(|| a * 3 + 2)).unwrap(),
+            "| b | (b - 2) / 3"
+        );
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert!(matches!(
+            eq.solve_to_string(&parse_quote!(|| c + 2)),
+            Err(super::ParseError::NoSolveFor)
+        ));
+    }
+
+    #[test]
+    fn invert_with_other_attributes() {
+        /// Converts a reading back to its raw value.
+        #[derive(ClosureInverter, Debug)]
+        #[allow(dead_code)]
+        #[invert("|| a * 5 + 3")]
+        #[doc(alias = "Reading")]
+        struct Documented;
+        assert_eq!(Documented::calculate(28), 5);
+    }
+
+    #[test]
+    fn invert_generic_struct() {
+        use std::marker::PhantomData;
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a * 2 + 1", extra_bounds = "U: Copy")]
+        struct Convert<'a, T: Default, U>(PhantomData<&'a (T, U)>)
+        where
+            T: Clone;
+        assert_eq!(<Convert<u8, u16> as Invertible>::calculate(11), 5);
+    }
+
+    #[test]
+    fn invert_with_method_name() {
+        #[derive(ClosureInverter)]
+        #[invert("|| a * 4 + 2", method = "decode", variants = ["plain", "checked"])]
+        struct Encoded;
+        assert_eq!(Encoded::decode(14), 3);
+        assert_eq!(Encoded::decode_checked(14), Some(3));
+    }
+
+    #[test]
+    fn invert_several_attributes() {
+        /// Documentation comments are attributes too
+        #[derive(ClosureInverter)]
+        #[invert("|| a + 2", name = "sub_two")]
+        #[invert("|| a * 3", name = "third", variants = ["plain", "checked"])]
+        struct Test;
+        assert_eq!(Test::sub_two(7), 5);
+        assert_eq!(Test::third(9), 3);
+        assert_eq!(Test::third_checked(9), Some(3));
+    }
+
+    #[test]
+    fn reject_modulo() {
+        for formula in ["|| a % 3", "|| 10 % a + 1"] {
+            let error = super::ClosureInverter::from_formula("a", formula).unwrap_err();
+            assert_eq!(error.to_string(), "the modulo operator cannot be inverted");
+        }
+    }
+
+    #[test]
+    fn invert_string_to_string() {
+        assert_eq!(
+            super::invert_str("|| 100 / x", "x").unwrap(),
+            "| value | 100 / value"
+        );
+        assert!(matches!(
+            super::invert_str("|| a * a", "a"),
+            Err(super::ParseError::NonAffine { degree: 2, .. })
+        ));
+        assert!(matches!(
+            super::invert_str("a + 2", "a"),
+            Err(super::ParseError::Syntax(_))
+        ));
+    }
+
+    #[test]
+    fn invert_string_styled() {
+        let formula = "|| (a * 3 + 7) / 2";
+        assert_eq!(
+            super::invert_str_styled(formula, "a", super::Style::Pretty).unwrap(),
+            "|value| (value * 2 - 7) / 3"
+        );
+        assert_eq!(
+            super::invert_str_styled(formula, "a", super::Style::Compact).unwrap(),
+            "|value|(value*2-7)/3"
+        );
+    }
+
+    #[test]
+    fn validate_batch_of_formulas() {
+        let inverse = super::ClosureInverter::from_formula("a", "|| a * 2 + 1").unwrap();
+        assert_eq!(inverse, parse_quote!(|value| (value - 1) / 2));
+
+        let failures = super::ClosureInverter::validate_formulas(&[
+            ("a", "|| a + 2"),
+            ("a", "|| a * a"),
+            ("x", "|| 3 * x - 1"),
+            ("a", "|| 4 +"),
+            ("a", "|| b + 2"),
+        ]);
+        let indices: Vec<usize> = failures.iter().map(|(i, _)| *i).collect();
+        assert_eq!(indices, [1, 3, 4]);
+        assert!(matches!(
+            failures[0].1,
+            super::ParseError::NonAffine { degree: 2, .. }
+        ));
+        assert!(matches!(failures[1].1, super::ParseError::Syntax(_)));
+        assert!(matches!(failures[2].1, super::ParseError::NoSolveFor));
+    }
+
+    #[test]
+    fn invert_with_nonzero_input() {
+        use std::num::NonZeroUsize;
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| 100 / a", nonzero_input = true)]
+        struct Test;
+        assert_eq!(Test::calculate(NonZeroUsize::new(4).unwrap()), 25);
+        assert_eq!(Test::calculate(NonZeroUsize::MIN), 100);
+    }
+
+    #[test]
+    fn invert_step_by_step() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| 100 - a * 3 + 2", steps = true)]
+        struct Test;
+        assert_eq!(Test::calculate(72), 10);
+        let steps: Vec<(String, usize)> = Test::calculate_steps(72);
+        assert_eq!(
+            steps,
+            [
+                ("x - 2".to_owned(), 70),
+                ("100 - x".to_owned(), 30),
+                ("x / 3".to_owned(), 10),
+            ]
+        );
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| -a * 2 + 3", ty = "i32", steps = true)]
+        struct Negated;
+        assert_eq!(Negated::calculate(-7), 5);
+        let steps: Vec<(String, i32)> = Negated::calculate_steps(-7);
+        assert_eq!(
+            steps,
+            [
+                ("x - 3".to_owned(), -10),
+                ("x / 2".to_owned(), -5),
+                ("-x".to_owned(), 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn detect_overflowing_inverse() {
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let u8_ty: syn::Type = parse_quote!(u8);
+        let fraction = eq
+            .overflow_fraction(&parse_quote!(|| a + u8::MAX), &u8_ty)
+            .unwrap()
+            .unwrap();
+        assert_eq!(fraction, 255.0 / 256.0);
+
+        let fraction = eq
+            .overflow_fraction(&parse_quote!(|| a * 2 + 1), &u8_ty)
+            .unwrap()
+            .unwrap();
+        assert_eq!(fraction, 1.0 / 256.0);
+
+        let fraction = eq
+            .overflow_fraction(&parse_quote!(|| a + 1), &parse_quote!(u64))
+            .unwrap()
+            .unwrap();
+        assert!(fraction < 0.01);
+
+        assert_eq!(
+            eq.overflow_fraction(&parse_quote!(|| a + SCALE), &u8_ty)
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn no_overflow_warning_for_rare_overflow() {
+        // The warning is a use of a deprecated item, which this would make an error
+        #[deny(deprecated)]
+        mod quiet {
+            use proc_lineq_derive::ClosureInverter;
+
+            #[derive(ClosureInverter)]
+            #[invert(expr = "|| a * 2 + 1", ty = "u8", overflow_check = "warn")]
+            pub struct Test;
+        }
+        assert_eq!(quiet::Test::calculate(7), 3);
+    }
+
+    #[test]
+    fn invert_cast_target() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| (a as i64) + 2", ty = "i64")]
+        struct Test;
+        assert_eq!(Test::calculate(7), 5);
+        assert_eq!(Test::calculate(-3), -5);
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let inverse = eq.solve(&parse_quote!(|| (a as i64) + 2)).unwrap();
+        assert_eq!(*inverse.body, parse_quote!((b - 2) as _));
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert!(matches!(
+            eq.solve(&parse_quote!(|| (a as i32 as i64) + 2)),
+            Err(super::ParseError::NonInvertibleOp(..))
+        ));
+    }
+
+    #[test]
+    fn invert_with_factory() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a * SCALE + OFFSET", factory = "make_inverse")]
+        struct Test;
+        let inverse = Test::make_inverse(3, 1);
+        assert_eq!(inverse(16), 5);
+        let inverse = Test::make_inverse(10, 0);
+        assert_eq!(inverse(50), 5);
+
+        // Keywords in lower case are taken as raw identifiers
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a * TYPE + FN", factory = "make_inverse")]
+        struct Keywords;
+        let inverse = Keywords::make_inverse(3, 1);
+        assert_eq!(inverse(16), 5);
+    }
+
+    #[test]
+    fn render_error() {
+        let source = "|| a * a";
+        let error = super::ClosureInverter::from_formula("a", source).unwrap_err();
+        assert_eq!(
+            error.render(source),
+            "error: formula is degree 2 in `a`\n \
+             --> formula\n  \
+             |\n\
+             1 | || a * a\n  \
+             | ^^^^^^^^\n"
+        );
+    }
+
+    #[test]
+    fn render_error_at_token() {
+        let source = "|| a % 2";
+        let error = super::ClosureInverter::from_formula("a", source).unwrap_err();
+        assert_eq!(
+            error.render(source),
+            "error: the modulo operator cannot be inverted\n \
+             --> formula\n  \
+             |\n\
+             1 | || a % 2\n  \
+             |      ^\n"
+        );
+
+        // Only the line holding the error is marked
+        let source = "|| a * 2\n    + a.foo()";
+        let error = super::ClosureInverter::from_formula("a", source).unwrap_err();
+        assert!(error
+            .render(source)
+            .ends_with("1 | || a * 2\n2 |     + a.foo()\n  |       ^^^^^^^\n"));
+    }
+
+    #[test]
+    fn invert_with_shifts() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a * 8 + 3", optimize = true, steps = true)]
+        struct Test;
+        assert_eq!(Test::calculate(43), 5);
+        assert_eq!(Test::calculate(44), 5);
+        let steps: Vec<String> = Test::calculate_steps(43)
+            .into_iter()
+            .map(|(label, _)| label)
+            .collect();
+        assert_eq!(steps, ["x - 3", "x >> 3"]);
+
+        // Not a power of two, so the division is kept
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a * 6", optimize = true, steps = true)]
+        struct Test2;
+        assert_eq!(Test2::calculate(30), 5);
+        assert_eq!(Test2::calculate_steps(30)[0].0, "x / 6");
+    }
+
+    #[test]
+    fn invert_disjoint_or() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a | 0xF0", ty = "u8", disjoint = true)]
+        struct Test;
+        assert_eq!(Test::calculate(0xF5), 0x05);
+        assert_eq!(Test::calculate(0xF0), 0);
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| (a | 0x100) * 2 + 1", ty = "u16", disjoint = true)]
+        struct Test2;
+        assert_eq!(Test2::calculate(0x20B), 0x05);
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert!(matches!(
+            eq.solve(&parse_quote!(|| a | 0xF0)),
+            Err(super::ParseError::NonInvertibleOp(..))
+        ));
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b")).disjoint();
+        let inverse = eq.solve(&parse_quote!(|| 0xF0 | a)).unwrap();
+        assert_eq!(*inverse.body, parse_quote!(b & !0xF0));
+    }
+
+    #[test]
+    fn invert_optional_input() {
+        assert_eq!(Optional::calculate(Some(14)), Some(2));
+        assert_eq!(Optional::calculate(None), None);
+
+        // An input of `None` and an overflowing input both give `None`
+        assert_eq!(Optional::calculate_checked(Some(14)), Some(2));
+        assert_eq!(Optional::calculate_checked(Some(4)), None);
+        assert_eq!(Optional::calculate_checked(None), None);
+    }
+
+    #[test]
+    fn invert_with_doc_samples() {
+        #[derive(ClosureInverter)]
+        #[invert(
+            expr = "|| a * 2 + 1",
+            samples = [(7, 3), (11, 5)],
+            gen_doctest = true
+        )]
+        struct Test;
+        assert_eq!(Test::calculate(7), 3);
+        assert_eq!(Test::calculate(11), 5);
+    }
+
+    #[test]
+    fn invert_with_post_fn() {
+        fn clamp_to_byte(value: usize) -> usize {
+            value.min(255)
+        }
+
+        #[derive(ClosureInverter)]
+        #[invert(
+            expr = "|| a / 4",
+            post_fn = "clamp_to_byte",
+            variants = ["plain", "checked"]
+        )]
+        struct Test;
+        assert_eq!(Test::calculate(10), 40);
+        assert_eq!(Test::calculate(100), 255);
+        assert_eq!(Test::calculate_checked(100), Some(255));
+    }
+
+    #[test]
+    fn store_bijections() {
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a * 9 / 5 + 32", bijection_struct = true)]
+        struct Fahrenheit;
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a + 273", bijection_struct = true)]
+        struct Kelvin;
+
+        let mut scales = std::collections::HashMap::new();
+        scales.insert("fahrenheit", Fahrenheit::BIJECTION);
+        scales.insert("kelvin", Kelvin::BIJECTION);
+        assert_eq!(scales["fahrenheit"].forward(100), 212);
+        assert_eq!(scales["fahrenheit"].inverse(212), 100);
+        assert_eq!(scales["kelvin"].forward(27), 300);
+        assert_eq!(scales["kelvin"].inverse(300), 27);
+    }
+
+    #[test]
+    fn invert_named_target() {
+        #[derive(ClosureInverter)]
+        #[invert("|| x + 2", solve_for = "x")]
+        struct Test;
+        assert_eq!(Test::calculate(7), 5);
+
+        const OFFSET: usize = 1;
+        #[derive(ClosureInverter)]
+        #[invert("|| velocity_2 * 3 + OFFSET", solve_for = "velocity_2")]
+        struct Named;
+        assert_eq!(Named::calculate(10), 3);
+    }
+
+    #[test]
+    fn interpret_ir() {
+        use crate::Op;
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| 100 - 600 / (a * 3 + 4)", emit_ir = true)]
+        struct Test;
+
+        fn run(ops: &[Op<usize>], mut x: usize) -> usize {
+            for op in ops {
+                x = match *op {
+                    Op::AddConst(n) => x + n,
+                    Op::SubConst(n) => x - n,
+                    Op::SubFrom(n) => n - x,
+                    Op::MulConst(n) => x * n,
+                    Op::DivConst(n) => x / n,
+                    Op::ReciprocalInto(n) => n / x,
+                };
+            }
+            x
+        }
+
+        assert_eq!(
+            Test::OPS,
+            [
+                Op::SubFrom(100),
+                Op::ReciprocalInto(600),
+                Op::SubConst(4),
+                Op::DivConst(3),
+            ]
+        );
+        for b in [40, 90, 95, 99] {
+            assert_eq!(run(Test::OPS, b), Test::calculate(b));
+        }
+    }
+
+    #[test]
+    fn invert_zero_multiplied_subtree() {
+        #[derive(ClosureInverter)]
+        #[invert("|| a + 0 * (a + 5)")]
+        struct Test;
+        assert_eq!(Test::calculate(7), 7);
+
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert!(matches!(
+            eq.solve(&parse_quote!(|| 0 * a + 3)),
+            Err(super::ParseError::TargetEliminated(_))
+        ));
+    }
+
+    #[test]
+    fn reject_target_divided_by_itself() {
+        for closure in [parse_quote!(|| a / a), parse_quote!(|| a / a + 2)] {
+            let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+            let error = eq.solve(&closure).unwrap_err();
+            assert!(matches!(error, super::ParseError::TargetEliminated(_)));
+            assert_eq!(
+                error.to_string(),
+                "the result does not depend on the target, as it is divided by itself"
+            );
+        }
+
+        // Not the same expression, so the target is not eliminated
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert!(matches!(
+            eq.solve(&parse_quote!(|| a / (a + 1))),
+            Err(super::ParseError::Multiple(_))
+        ));
+    }
+
+    #[test]
+    fn reject_divide_by_zero() {
+        for formula in [
+            "|| a / 0",
+            "|| a * 0.0 + 1.0",
+            "|| (a + 1) / (0)",
+            "|| 0.0 * a",
+        ] {
+            let closure: ExprClosure = syn::parse_str(formula).unwrap();
+            let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+            let error = eq.solve(&closure).unwrap_err();
+            assert!(
+                matches!(error, super::ParseError::DivideByZero(_)),
+                "{}",
+                formula
+            );
+            assert_eq!(
+                error.to_string(),
+                "the target is multiplied or divided by a literal zero"
+            );
+        }
+
+        // Only a zero is rejected, not a fraction
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        assert!(eq.solve(&parse_quote!(|| a * 0.5)).is_ok());
+    }
+
+    #[test]
+    fn error_spans() {
+        for (formula, marked) in [
+            ("|| a % 3", "%"),
+            ("|| a.foo()", "a.foo()"),
+            ("|| a / (a + 1)", "a / (a + 1)"),
+            ("|| a / 0", "0"),
+            ("|| a + [1][0]", "[1][0]"),
+        ] {
+            let closure: ExprClosure = syn::parse_str(formula).unwrap();
+            let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+            let span = eq.solve(&closure).unwrap_err().span().unwrap();
+            assert_eq!(&formula[span.byte_range()], marked, "{}", formula);
+        }
+
+        // Nothing to point at when the target is missing altogether
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let error = eq.solve(&parse_quote!(|| c + 2)).unwrap_err();
+        assert!(matches!(error, super::ParseError::NoSolveFor));
+        assert!(error.span().is_none());
+    }
+
+    #[test]
+    fn reject_unsupported_expressions() {
+        let a = format_ident!("a");
+        for formula in [
+            "|| a.foo() + 1",
+            "|| match a { _ => 1 } + 1",
+            "|| [a][0] * 2",
+            "|| a.0 + 1",
+            "|| foo(a) + 1",
+        ] {
+            assert!(
+                super::ClosureInverter::from_formula("a", formula).is_err(),
+                "{}",
+                formula
+            );
+            let closure: ExprClosure = syn::parse_str(formula).unwrap();
+            let body = super::ClosureInverter::body(&closure).unwrap();
+            assert!(
+                super::ClosureInverter::check_contains_target(body, &a),
+                "{}",
+                formula
+            );
+
+            // Peeled directly, without the validation solving does first
+            let mut eq = super::ClosureInverter::new(a.clone(), format_ident!("b"));
+            let containing = super::Containing::new(body, &a);
+            let Expr::Binary(b) = body else {
+                unreachable!()
+            };
+            let error = eq.peel((*b.left).clone(), &containing).unwrap_err();
+            assert!(
+                matches!(error, super::ParseError::Validation(_)),
+                "{}",
+                formula
+            );
+        }
+        assert!(!super::ClosureInverter::check_contains_target(
+            &parse_quote!(match b {
+                _ => [1][0],
+            }),
+            &a
+        ));
+    }
+
+    #[test]
+    fn reject_cancelled_target() {
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let error = eq.solve(&parse_quote!(|| (a - a) + 5)).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "the result does not depend on the target, as it cancels out, leaving the constant output `5`"
+        );
+    }
+
+    #[test]
+    fn invert_zero_multiplied_target() {
+        #[derive(ClosureInverter)]
+        #[invert("|| a + 0 * a")]
+        struct Test;
+        assert_eq!(Test::calculate(5), 5);
+        assert_eq!(Test::calculate(3), 3);
+    }
+
+    #[test]
+    fn extract_constants() {
+        let eq = super::ClosureInverter::new(format_ident!("a"), format_ident!("b"));
+        let closure: ExprClosure = parse_quote!(|| a * 9 / 5 + 32);
+        let expected: Vec<Expr> = vec![parse_quote!(9), parse_quote!(5), parse_quote!(32)];
+        assert_eq!(eq.constants(&closure).unwrap(), expected);
+
+        let closure: ExprClosure = parse_quote!(|| SCALE * a - 3);
+        let expected: Vec<Expr> = vec![parse_quote!(SCALE), parse_quote!(3)];
+        assert_eq!(eq.constants(&closure).unwrap(), expected);
+    }
 }
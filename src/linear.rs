@@ -0,0 +1,114 @@
+use crate::fold::{int_lit, int_value};
+use crate::ClosureInverter;
+use proc_macro2::Ident;
+use syn::{parse_quote, BinOp, Expr, UnOp};
+
+/// A linear expression `coeff * target + offset`, with `None` as a zero offset.
+struct Linear {
+    coeff: i128,
+    offset: Option<Expr>,
+}
+
+/// Collects like terms of the target, so `2 * a + 3 + a` becomes `a * 3 + (3)`.
+///
+/// Only additions, subtractions, negation and multiplication by integer literals are
+/// collected, as the terms of a division do not divide separately under integer arithmetic.
+/// Returns `None` for anything else, or when the terms cancel.
+pub(crate) fn collect_like_terms(e: &Expr, target: &Ident) -> Option<Expr> {
+    let Linear { coeff, offset } = linear(e, target)?;
+    let magnitude = int_lit(coeff.unsigned_abs(), None);
+    let term: Expr = match coeff.unsigned_abs() {
+        0 => return None,
+        1 => parse_quote!(#target),
+        _ => parse_quote!(#target * #magnitude),
+    };
+    Some(match (coeff > 0, offset) {
+        (true, None) => term,
+        (true, Some(offset)) => parse_quote!(#term + (#offset)),
+        (false, None) => parse_quote!(-(#term)),
+        (false, Some(offset)) => parse_quote!((#offset) - #term),
+    })
+}
+
+fn linear(e: &Expr, target: &Ident) -> Option<Linear> {
+    if !ClosureInverter::check_contains_target(e, target) {
+        return Some(Linear {
+            coeff: 0,
+            offset: Some(e.clone()),
+        });
+    }
+    match e {
+        Expr::Path(p) if p.path.is_ident(target) => Some(Linear {
+            coeff: 1,
+            offset: None,
+        }),
+        Expr::Paren(p) => linear(&p.expr, target),
+        Expr::Group(g) => linear(&g.expr, target),
+        Expr::Unary(u) if matches!(u.op, UnOp::Neg(_)) => {
+            let Linear { coeff, offset } = linear(&u.expr, target)?;
+            Some(Linear {
+                coeff: coeff.checked_neg()?,
+                offset: offset.map(|offset| parse_quote!(-(#offset))),
+            })
+        }
+        Expr::Binary(b) => match b.op {
+            BinOp::Add(_) | BinOp::Sub(_) => {
+                let left = linear(&b.left, target)?;
+                let right = linear(&b.right, target)?;
+                let add = matches!(b.op, BinOp::Add(_));
+                let coeff = if add {
+                    left.coeff.checked_add(right.coeff)?
+                } else {
+                    left.coeff.checked_sub(right.coeff)?
+                };
+                let offset = match (left.offset, right.offset) {
+                    (left, None) => left,
+                    (None, Some(right)) if add => Some(right),
+                    (None, Some(right)) => Some(parse_quote!(-(#right))),
+                    (Some(left), Some(right)) if add => Some(parse_quote!(#left + (#right))),
+                    (Some(left), Some(right)) => Some(parse_quote!(#left - (#right))),
+                };
+                Some(Linear { coeff, offset })
+            }
+            BinOp::Mul(_) => {
+                let (literal, term) = match int_value(&b.left) {
+                    Some(_) => (&b.left, &b.right),
+                    None => (&b.right, &b.left),
+                };
+                let factor = i128::try_from(int_value(literal)?).ok()?;
+                let Linear { coeff, offset } = linear(term, target)?;
+                Some(Linear {
+                    coeff: coeff.checked_mul(factor)?,
+                    offset: offset.map(|offset| parse_quote!((#offset) * #literal)),
+                })
+            }
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quote::format_ident;
+
+    #[test]
+    fn collect_terms_of_target() {
+        let a = format_ident!("a");
+        let e: Expr = parse_quote!(a + a);
+        assert_eq!(collect_like_terms(&e, &a), Some(parse_quote!(a * 2)));
+
+        let e: Expr = parse_quote!(2 * a + 3 + a);
+        assert_eq!(collect_like_terms(&e, &a), Some(parse_quote!(a * 3 + (3))));
+
+        let e: Expr = parse_quote!(10 - a * 3 + a);
+        assert_eq!(collect_like_terms(&e, &a), Some(parse_quote!((10) - a * 2)));
+
+        let e: Expr = parse_quote!(a * a);
+        assert_eq!(collect_like_terms(&e, &a), None);
+
+        let e: Expr = parse_quote!((a + a) / 2);
+        assert_eq!(collect_like_terms(&e, &a), None);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{parse_ident, ClosureInverter, ParseError};
+use proc_macro2::{Ident, Span};
+use syn::{parse_quote, BinOp, Expr, ExprBinary, ExprClosure};
+
+/// Parses a whitespace separated reverse-Polish expression such as `a 2 + 3 *` into an `Expr`.
+///
+/// Operands are integer or float literals and identifiers, operators are `+`, `-`, `*` and `/`.
+/// Errors name the byte offset of the token they were found at.
+pub(crate) fn parse_rpn(rpn: &str) -> Result<Expr, ParseError> {
+    let mut stack: Vec<Expr> = vec![];
+    for token in rpn.split_whitespace() {
+        // The token is a slice of `rpn`, so its offset is the distance between their starts
+        let offset = token.as_ptr() as usize - rpn.as_ptr() as usize;
+        if matches!(token, "+" | "-" | "*" | "/") {
+            let op =
+                syn::parse_str::<BinOp>(token).map_err(|e| ParseError::Syntax(e.to_string()))?;
+            let right = stack.pop();
+            let left = stack.pop();
+            match (left, right) {
+                (Some(left), Some(right)) => stack.push(Expr::Binary(ExprBinary {
+                    attrs: vec![],
+                    left: Box::new(left),
+                    op,
+                    right: Box::new(right),
+                })),
+                _ => {
+                    return Err(ParseError::Syntax(format!(
+                        "operator `{}` at byte {} is missing an operand",
+                        token, offset
+                    )))
+                }
+            }
+        } else if syn::parse_str::<BinOp>(token).is_ok() {
+            return Err(ParseError::Syntax(format!(
+                "unsupported operator `{}` at byte {}, expected `+`, `-`, `*` or `/`",
+                token, offset
+            )));
+        } else {
+            match syn::parse_str::<Expr>(token) {
+                Ok(e @ (Expr::Lit(_) | Expr::Path(_))) => stack.push(e),
+                _ => {
+                    return Err(ParseError::Syntax(format!(
+                        "unexpected token `{}` at byte {}",
+                        token, offset
+                    )))
+                }
+            }
+        }
+    }
+    match (stack.pop(), stack.is_empty()) {
+        (Some(e), true) => Ok(e),
+        (None, _) => Err(ParseError::Syntax("empty expression".to_owned())),
+        (Some(_), false) => Err(ParseError::Syntax(
+            "operands are left without an operator".to_owned(),
+        )),
+    }
+}
+
+impl ClosureInverter {
+    /// Inverts a formula written in reverse-Polish notation, e.g. `a 2 +`, for `solve_for`.
+    ///
+    /// The returned closure takes a single parameter named `value`.
+    pub fn from_rpn(solve_for: &str, rpn: &str) -> Result<ExprClosure, ParseError> {
+        let solve_for = parse_ident(solve_for)?;
+        let body = parse_rpn(rpn)?;
+        let closure = parse_quote!(|| #body);
+        ClosureInverter::new(solve_for, Ident::new("value", Span::call_site())).solve(&closure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invert_rpn() {
+        let inverse = ClosureInverter::from_rpn("a", "a 2 +").unwrap();
+        assert_eq!(inverse, parse_quote!(|value| value - 2));
+
+        let inverse = ClosureInverter::from_rpn("x", "x 3 * 7 +").unwrap();
+        assert_eq!(inverse, parse_quote!(|value| (value - 7) / 3));
+
+        let inverse = ClosureInverter::from_rpn("a", "100 a /").unwrap();
+        assert_eq!(inverse, parse_quote!(|value| 100 / value));
+    }
+
+    #[test]
+    fn reject_malformed_rpn() {
+        assert!(matches!(
+            ClosureInverter::from_rpn("a", "a +"),
+            Err(ParseError::Syntax(_))
+        ));
+        assert!(matches!(
+            ClosureInverter::from_rpn("a", "a 2 3 +"),
+            Err(ParseError::Syntax(_))
+        ));
+        assert!(matches!(
+            ClosureInverter::from_rpn("a", ""),
+            Err(ParseError::Syntax(_))
+        ));
+
+        // Only the arithmetic operators are accepted, with the position of any other
+        for (rpn, message) in [
+            (
+                "a 2 ==",
+                "unsupported operator `==` at byte 4, expected `+`, `-`, `*` or `/`",
+            ),
+            (
+                "a  b &&",
+                "unsupported operator `&&` at byte 5, expected `+`, `-`, `*` or `/`",
+            ),
+            (
+                "a 3 %",
+                "unsupported operator `%` at byte 4, expected `+`, `-`, `*` or `/`",
+            ),
+            ("a 2 + @", "unexpected token `@` at byte 6"),
+        ] {
+            assert!(
+                matches!(
+                    ClosureInverter::from_rpn("a", rpn),
+                    Err(ParseError::Syntax(e)) if e == message
+                ),
+                "{}",
+                rpn
+            );
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{parse_ident, ClosureInverter, ParseError};
+use proc_macro2::{Ident, Span};
+use std::iter::Peekable;
+use syn::{parse_quote, BinOp, Expr, ExprBinary, ExprClosure, ExprParen};
+
+/// Parses an S-expression such as `(+ (* a 3) 7)` into an `Expr`.
+///
+/// Operators are `+`, `-`, `*` and `/`. With more than two operands they apply left to right,
+/// so `(- a 1 2)` is `a - 1 - 2`, and `(- a)` alone negates. Operands are integer or float
+/// literals, identifiers and nested lists.
+pub(crate) fn parse_sexpr(sexpr: &str) -> Result<Expr, ParseError> {
+    let spaced = sexpr.replace('(', " ( ").replace(')', " ) ");
+    let mut tokens = spaced.split_whitespace().peekable();
+    let e = parse_operand(&mut tokens)?;
+    match tokens.next() {
+        None => Ok(e),
+        Some(token) => Err(ParseError::Syntax(format!(
+            "unexpected `{}` after the expression",
+            token
+        ))),
+    }
+}
+
+fn parse_operand<'a>(
+    tokens: &mut Peekable<impl Iterator<Item = &'a str>>,
+) -> Result<Expr, ParseError> {
+    match tokens.next() {
+        Some("(") => parse_list(tokens),
+        Some(")") => Err(ParseError::Syntax("unexpected `)`".to_owned())),
+        Some(token) => match syn::parse_str::<Expr>(token) {
+            Ok(e @ (Expr::Lit(_) | Expr::Path(_))) => Ok(e),
+            _ => Err(ParseError::Syntax(format!("unexpected token `{}`", token))),
+        },
+        None => Err(ParseError::Syntax("empty expression".to_owned())),
+    }
+}
+
+/// Parses the rest of a list after its opening parenthesis.
+fn parse_list<'a>(
+    tokens: &mut Peekable<impl Iterator<Item = &'a str>>,
+) -> Result<Expr, ParseError> {
+    let op = match tokens.next() {
+        Some(token @ ("+" | "-" | "*" | "/")) => {
+            syn::parse_str::<BinOp>(token).map_err(|e| ParseError::Syntax(e.to_string()))?
+        }
+        Some(token) => {
+            return Err(ParseError::Syntax(format!(
+                "expected an operator, found `{}`",
+                token
+            )))
+        }
+        None => return Err(ParseError::Syntax("unclosed `(`".to_owned())),
+    };
+    let mut operands = vec![];
+    loop {
+        match tokens.peek() {
+            Some(&")") => {
+                tokens.next();
+                break;
+            }
+            Some(_) => operands.push(parse_operand(tokens)?),
+            None => return Err(ParseError::Syntax("unclosed `(`".to_owned())),
+        }
+    }
+    let mut operands = operands.into_iter();
+    let first = operands
+        .next()
+        .ok_or_else(|| ParseError::Syntax("operator without operands".to_owned()))?;
+    let e = match (op, operands.len()) {
+        (BinOp::Sub(_), 0) => parse_quote!(-#first),
+        (_, 0) => {
+            return Err(ParseError::Syntax(
+                "operator with a single operand".to_owned(),
+            ))
+        }
+        _ => operands.fold(first, |left, right| {
+            Expr::Binary(ExprBinary {
+                attrs: vec![],
+                left: Box::new(left),
+                op,
+                right: Box::new(right),
+            })
+        }),
+    };
+    // Keeps the grouping when the expression is written back out as tokens
+    Ok(Expr::Paren(ExprParen {
+        attrs: vec![],
+        paren_token: Default::default(),
+        expr: Box::new(e),
+    }))
+}
+
+impl ClosureInverter {
+    /// Inverts a formula written as an S-expression, e.g. `(+ (* a 3) 7)`, for `solve_for`.
+    ///
+    /// The returned closure takes a single parameter named `value`.
+    pub fn from_sexpr(solve_for: &str, sexpr: &str) -> Result<ExprClosure, ParseError> {
+        let solve_for = parse_ident(solve_for)?;
+        let body = parse_sexpr(sexpr)?;
+        let closure = parse_quote!(|| #body);
+        ClosureInverter::new(solve_for, Ident::new("value", Span::call_site())).solve(&closure)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invert_sexpr() {
+        let inverse = ClosureInverter::from_sexpr("a", "(+ (* a 3) 7)").unwrap();
+        assert_eq!(inverse, parse_quote!(|value| (value - 7) / 3));
+
+        let inverse = ClosureInverter::from_sexpr("x", "(/ 100 (- x 1 2))").unwrap();
+        assert_eq!(inverse, parse_quote!(|value| 100 / value + 3));
+
+        let inverse = ClosureInverter::from_sexpr("a", "(* (- a) (- k 2))").unwrap();
+        assert_eq!(inverse, parse_quote!(|value| -(value / (k - 2))));
+    }
+
+    #[test]
+    fn reject_malformed_sexpr() {
+        for sexpr in ["(+ a 2", "(+ a 2))", "(a 2)", "(* a)", "()", "", "(% a 2)"] {
+            assert!(
+                matches!(
+                    ClosureInverter::from_sexpr("a", sexpr),
+                    Err(ParseError::Syntax(_))
+                ),
+                "{}",
+                sexpr
+            );
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! Defaults apply to the derives within `invert_defaults!` and to nothing after it.
+
+use proc_lineq_derive::ClosureInverter;
+
+proc_lineq_derive::invert_defaults! {
+    ty = "i64", debug_bounds = true;
+
+    #[derive(ClosureInverter)]
+    #[invert("|| a * 3 - 4")]
+    struct Defaulted;
+
+    #[derive(ClosureInverter)]
+    #[invert(expr = "|| a * 3 - 4", ty = "u8")]
+    struct Overridden;
+
+    mod nested {
+        use proc_lineq_derive::ClosureInverter;
+
+        #[derive(ClosureInverter)]
+        #[invert(expr = "|| a + 1")]
+        pub struct Nested;
+    }
+}
+
+#[derive(ClosureInverter)]
+#[invert("|| a * 3 - 4")]
+struct Undefaulted;
+
+#[test]
+fn apply_default_type() {
+    let value: i64 = Defaulted::calculate(-10);
+    assert_eq!(value, -2);
+
+    let value: u8 = Overridden::calculate(11);
+    assert_eq!(value, 5);
+
+    let value: i64 = nested::Nested::calculate(-1);
+    assert_eq!(value, -2);
+
+    let value: usize = Undefaulted::calculate(11);
+    assert_eq!(value, 5);
+}
@@ -0,0 +1,59 @@
+//! The generated code refers only to `core` and the items of `proc_lineq` built without its
+//! `solver` feature, so the derive can be used in a `no_std` crate depending on `proc-lineq` with
+//! `default-features = false`. Run with `cargo test --no-default-features --test no_std` to build
+//! `proc_lineq` as it would be there, as a `no_std` crate without `syn`.
+
+#![no_std]
+
+use proc_lineq_derive::ClosureInverter;
+
+#[derive(ClosureInverter)]
+#[invert(
+    expr = "|| a * 3 + 2",
+    variants = ["plain", "checked"],
+    debug_bounds = true,
+    domain = "0..=100",
+    forward = true,
+    verify = true,
+    bijection_struct = true,
+    emit_ir = true
+)]
+struct Plain;
+
+#[derive(ClosureInverter)]
+#[invert(expr = "|| a * 4", exact = true)]
+struct Exact;
+
+#[derive(ClosureInverter)]
+#[invert(expr = "|| a - 1", option_input = true, nonzero_input = false)]
+struct Optional;
+
+#[derive(ClosureInverter)]
+#[invert(expr = "|| 100 / a", ty = "u32", nonzero_input = true)]
+struct NonZero;
+
+#[derive(ClosureInverter)]
+#[invert(
+    expr = "|| -a + 7",
+    ty = "i32",
+    forward = true,
+    gen_test = "roundtrip_negated"
+)]
+struct Negated;
+
+#[test]
+fn derive_without_std() {
+    assert_eq!(Plain::calculate(14), 4);
+    assert_eq!(Plain::calculate_checked(14), Some(4));
+    assert_eq!(Plain::forward(4), 14);
+    assert_eq!(Plain::BIJECTION.inverse(14), 4);
+    assert!(!Plain::OPS.is_empty());
+    assert_eq!(Exact::calculate(12), Ok(3));
+    assert!(Exact::calculate(13).is_err());
+    assert_eq!(Optional::calculate(Some(4)), Some(5));
+    assert_eq!(
+        NonZero::calculate(core::num::NonZeroU32::new(20).unwrap()),
+        5
+    );
+    assert_eq!(Negated::calculate(Negated::forward(3)), 3);
+}